@@ -81,7 +81,7 @@ impl<T: Transformable> Ot<T> {
     }
 
     pub fn operations(&self, version: usize) -> Option<&[Operation]> {
-        if self.syncable(version) {
+        if !self.syncable(version) {
             return None;
         }
         let start = version - self.deleted;
@@ -92,3 +92,43 @@ impl<T: Transformable> Ot<T> {
         self.deleted + self.operations.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::Branch::*;
+    use crate::Path;
+
+    use super::*;
+
+    struct Noop;
+
+    impl Transformable for Noop {
+        fn try_apply(&mut self, _op: Operation) -> Result<Option<Operation>, Error> {
+            Ok(None)
+        }
+    }
+
+    fn ot_with_history(deleted: usize, len: usize) -> Ot<Noop> {
+        let mut ot = Ot::new(Noop);
+        ot.deleted = deleted;
+        ot.operations = (0..len)
+            .map(|i| Operation::Delete(Path(vec![Index(i)]), 1))
+            .collect();
+        ot
+    }
+
+    #[test]
+    fn operations_since_version() {
+        let ot = ot_with_history(2, 3);
+        assert_eq!(ot.operations(2).map(|ops| ops.len()), Some(3));
+        assert_eq!(ot.operations(4).map(|ops| ops.len()), Some(1));
+        assert_eq!(ot.operations(5).map(|ops| ops.len()), Some(0));
+    }
+
+    #[test]
+    fn operations_of_unsyncable_version() {
+        let ot = ot_with_history(2, 3);
+        assert!(ot.operations(1).is_none());
+        assert!(ot.operations(6).is_none());
+    }
+}
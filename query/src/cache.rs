@@ -0,0 +1,230 @@
+use polars::frame::DataFrame;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::time::UNIX_EPOCH;
+
+use crate::context::ExecutionContext;
+use crate::doc::Node;
+
+/// 512 MiB.
+pub const DEFAULT_CACHE_BUDGET: usize = 512 * 1024 * 1024;
+
+/// Fields that don't affect the result of a node.  Inputs are hashed by
+/// content instead of by id.
+const IGNORED_FIELDS: [&str; 8] = [
+    "id",
+    "position",
+    "outputs",
+    "input",
+    "left_input",
+    "right_input",
+    "primary_input",
+    "secondary_input"
+];
+
+struct CacheEntry {
+    frame: DataFrame,
+    size: usize,
+    last_used: u64
+}
+
+/// Results of nodes keyed by the hash of their subgraph (see `hash_nodes`).
+/// The least recently used results are evicted once the estimated size of
+/// the results exceeds the budget.
+pub struct ResultCache {
+    budget: usize,
+    size: usize,
+    clock: u64,
+    entries: HashMap<u64, CacheEntry>
+}
+
+impl ResultCache {
+    pub fn new(budget: usize) -> ResultCache {
+        ResultCache {
+            budget,
+            size: 0,
+            clock: 0,
+            entries: HashMap::new()
+        }
+    }
+
+    pub fn get(&mut self, key: u64) -> Option<DataFrame> {
+        self.clock += 1;
+        let clock = self.clock;
+        self.entries.get_mut(&key).map(|entry| {
+            entry.last_used = clock;
+            entry.frame.clone()
+        })
+    }
+
+    /// Results larger than the whole budget aren't cached.
+    pub fn insert(&mut self, key: u64, frame: DataFrame) {
+        let size = frame.estimated_size();
+        if size > self.budget {
+            return;
+        }
+        self.remove(key);
+        while self.size + size > self.budget {
+            let lru = self.entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| *key);
+            match lru {
+                Some(lru) => self.remove(lru),
+                None => break
+            }
+        }
+        self.clock += 1;
+        self.size += size;
+        self.entries.insert(key, CacheEntry { frame, size, last_used: self.clock });
+    }
+
+    pub fn remove(&mut self, key: u64) {
+        if let Some(entry) = self.entries.remove(&key) {
+            self.size -= entry.size;
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// The estimated size of the cached results in bytes.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+}
+
+impl Default for ResultCache {
+    fn default() -> ResultCache {
+        ResultCache::new(DEFAULT_CACHE_BUDGET)
+    }
+}
+
+/// Hash every node by its definition and the hashes of its inputs.  Source
+/// nodes also hash the size and modification time of their file, so an
+/// edited file gets a new hash.  Nodes with a missing input or that are part
+/// of a cycle don't get a hash.
+pub fn hash_nodes(nodes: &HashMap<String, Node>, context: &ExecutionContext) -> HashMap<String, u64> {
+    let mut hashes: HashMap<String, Option<u64>> = HashMap::new();
+
+    for id in nodes.keys() {
+        let mut nodes_to_hash = vec![id.clone()];
+
+        while let Some(id) = nodes_to_hash.last().cloned() {
+            if hashes.contains_key(&id) {
+                nodes_to_hash.pop();
+                continue;
+            }
+            let node = nodes.get(&id).unwrap();
+            let inputs = node.inputs();
+
+            let is_broken = inputs.iter().any(|input| match input {
+                Some(input) => {
+                    !nodes.contains_key(input)
+                        || nodes_to_hash.contains(input)
+                        || matches!(hashes.get(input), Some(None))
+                }
+                None => true
+            });
+
+            let hash = if is_broken {
+                None
+            } else {
+                let pending: Vec<&String> = inputs
+                    .iter()
+                    .copied()
+                    .flatten()
+                    .filter(|input| !hashes.contains_key(*input))
+                    .collect();
+                if !pending.is_empty() {
+                    nodes_to_hash.extend(pending.into_iter().cloned());
+                    continue;
+                }
+                let input_hashes: Vec<u64> = inputs
+                    .iter()
+                    .copied()
+                    .flatten()
+                    .map(|input| hashes.get(input).unwrap().unwrap())
+                    .collect();
+                hash_node(node, &input_hashes, context)
+            };
+            hashes.insert(id, hash);
+            nodes_to_hash.pop();
+        }
+    }
+
+    hashes
+        .into_iter()
+        .filter_map(|(id, hash)| hash.map(|hash| (id, hash)))
+        .collect()
+}
+
+/// Derive a key from `key` and a value, e.g. a sorted view of a result.
+pub fn hash_with<T: Serialize>(key: u64, value: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    serde_json::to_string(value)
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+fn hash_node(node: &Node, input_hashes: &[u64], context: &ExecutionContext) -> Option<u64> {
+    let mut value = serde_json::to_value(node).ok()?;
+    if let Some(fields) = value.as_object_mut() {
+        for field in IGNORED_FIELDS.iter() {
+            fields.remove(*field);
+        }
+    }
+
+    let mut hasher = DefaultHasher::new();
+    value.to_string().hash(&mut hasher);
+    input_hashes.hash(&mut hasher);
+
+    if let Some(filename) = node.source_filename() {
+        let metadata = fs::metadata(context.resolve(filename).ok()?).ok()?;
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        metadata.len().hash(&mut hasher);
+        modified.as_nanos().hash(&mut hasher);
+    }
+
+    Some(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use polars::prelude::NamedFrom;
+    use polars::prelude::Series;
+    use super::*;
+
+    fn frame(len: usize) -> DataFrame {
+        DataFrame::new(vec![Series::new("a", vec![0_i64; len])]).unwrap()
+    }
+
+    #[test]
+    fn evict_least_recently_used() {
+        let size = frame(100).estimated_size();
+        let mut cache = ResultCache::new(size * 2);
+        cache.insert(1, frame(100));
+        cache.insert(2, frame(100));
+        cache.get(1);
+        cache.insert(3, frame(100));
+        assert!(cache.get(1).is_some());
+        assert!(cache.get(2).is_none());
+        assert!(cache.get(3).is_some());
+        assert_eq!(cache.size(), size * 2);
+
+        cache.insert(4, frame(1000));
+        assert!(cache.get(4).is_none());
+        assert_eq!(cache.len(), 2);
+    }
+}
@@ -1,6 +1,9 @@
+use serde::Deserialize;
+use serde::Serialize;
+
 use crate::data_type::DataType;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Column {
     pub name: String,
     pub data_type: DataType
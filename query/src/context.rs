@@ -0,0 +1,157 @@
+use std::io::ErrorKind;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use std::sync::atomic::Ordering;
+
+use crate::error::PoldaError;
+
+/// The environment a query runs in.  Every file a query reads is resolved
+/// against the project directory and must stay inside it.
+#[derive(Debug, Clone)]
+pub struct ExecutionContext {
+    project_dir: Arc<PathBuf>,
+    token: CancellationToken
+}
+
+impl ExecutionContext {
+    pub fn new(project_dir: PathBuf) -> ExecutionContext {
+        ExecutionContext {
+            project_dir: Arc::new(project_dir),
+            token: CancellationToken::default()
+        }
+    }
+
+    pub fn project_dir(&self) -> &Path {
+        &self.project_dir
+    }
+
+    /// The same context for a query that can be canceled with `token`.
+    pub fn with_token(&self, token: CancellationToken) -> ExecutionContext {
+        ExecutionContext {
+            project_dir: self.project_dir.clone(),
+            token
+        }
+    }
+
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Return `PoldaError::Canceled` if the query has been canceled.
+    pub fn check_canceled(&self) -> Result<(), PoldaError> {
+        if self.token.is_canceled() {
+            Err(PoldaError::Canceled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Resolve a filename relative to the project directory.  The path is
+    /// canonicalized, so `..` components and symlinks that lead out of the
+    /// project directory are rejected.
+    pub fn resolve(&self, filename: &str) -> Result<PathBuf, PoldaError> {
+        if filename.is_empty() {
+            return Err(PoldaError::SandboxError(String::from("Filename can't be empty")));
+        }
+
+        let root = self.project_dir.canonicalize()?;
+        let path = match root.join(filename).canonicalize() {
+            Ok(path) => path,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                return Err(PoldaError::IoError(std::io::Error::new(
+                    ErrorKind::NotFound,
+                    format!("File \"{}\" doesn't exist", filename)
+                )));
+            }
+            Err(e) => return Err(e.into())
+        };
+
+        if !path.starts_with(&root) {
+            return Err(PoldaError::SandboxError(format!("\"{}\" is outside the project directory", filename)));
+        }
+        Ok(path)
+    }
+
+    /// Resolve a file that is about to be written.  The file doesn't have to
+    /// exist, but its directory does.
+    pub fn resolve_target(&self, filename: &str) -> Result<PathBuf, PoldaError> {
+        let path = Path::new(filename);
+        let name = match path.file_name() {
+            Some(name) => name,
+            None => return Err(PoldaError::SandboxError(format!("\"{}\" isn't a file name", filename)))
+        };
+        let dir = match path.parent().and_then(|dir| dir.to_str()) {
+            Some(dir) if !dir.is_empty() => self.resolve(dir)?,
+            _ => self.project_dir.canonicalize()?
+        };
+
+        let path = dir.join(name);
+        if path.exists() {
+            // The file may be a symlink that leads out of the project.
+            let filename = path.to_string_lossy().to_string();
+            return self.resolve(&filename);
+        }
+        Ok(path)
+    }
+}
+
+/// Cancels a running query.  Queries check the token between their steps,
+/// so a step that is already running (e.g. a Polars collect) still finishes.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken(Arc<AtomicBool>);
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_canceled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for ExecutionContext {
+    fn default() -> ExecutionContext {
+        ExecutionContext::new(PathBuf::from("."))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use super::*;
+
+    #[test]
+    fn resolve_paths_in_project_dir() {
+        let root = std::env::temp_dir().join(format!("polda-context-{}", std::process::id()));
+        let project_dir = root.join("project");
+        fs::create_dir_all(project_dir.join("data")).unwrap();
+        fs::write(project_dir.join("data/a.csv"), "a\n1\n").unwrap();
+        fs::write(root.join("secret.csv"), "a\n1\n").unwrap();
+
+        let context = ExecutionContext::new(project_dir.clone());
+        let resolved = context.resolve("data/a.csv").unwrap();
+        assert_eq!(resolved, project_dir.canonicalize().unwrap().join("data/a.csv"));
+        assert!(context.resolve("data/../data/a.csv").is_ok());
+        assert!(matches!(context.resolve("../secret.csv"), Err(PoldaError::SandboxError(_))));
+        let absolute = root.join("secret.csv");
+        assert!(matches!(context.resolve(absolute.to_str().unwrap()), Err(PoldaError::SandboxError(_))));
+        assert!(matches!(context.resolve("missing.csv"), Err(PoldaError::IoError(_))));
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(root.join("secret.csv"), project_dir.join("link.csv")).unwrap();
+            assert!(matches!(context.resolve("link.csv"), Err(PoldaError::SandboxError(_))));
+            assert!(matches!(context.resolve_target("link.csv"), Err(PoldaError::SandboxError(_))));
+        }
+
+        let target = context.resolve_target("data/b.csv").unwrap();
+        assert_eq!(target, project_dir.canonicalize().unwrap().join("data/b.csv"));
+        assert!(matches!(context.resolve_target("../b.csv"), Err(PoldaError::SandboxError(_))));
+        assert!(context.resolve_target("missing/b.csv").is_err());
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
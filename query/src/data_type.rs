@@ -1,4 +1,5 @@
 use polars::datatypes::DataType as PolarsDataType;
+use polars::datatypes::TimeUnit;
 use serde::Deserialize;
 use serde::Serialize;
 
@@ -26,22 +27,41 @@ pub enum DataType {
 }
 
 impl DataType {
+    pub fn is_numeric(&self) -> bool {
+        matches!(
+            self,
+            DataType::Float32
+                | DataType::Float64
+                | DataType::Int8
+                | DataType::Int16
+                | DataType::Int32
+                | DataType::Int64
+                | DataType::UInt8
+                | DataType::UInt16
+                | DataType::UInt32
+                | DataType::UInt64
+        )
+    }
+
     pub fn into_polars(&self) -> PolarsDataType {
         match self {
             DataType::Boolean => PolarsDataType::Boolean,
             DataType::Date => PolarsDataType::Date,
+            DataType::DateTime => PolarsDataType::Datetime(TimeUnit::Microseconds, None),
+            DataType::Duration => PolarsDataType::Duration(TimeUnit::Microseconds),
             DataType::Float32 => PolarsDataType::Float32,
             DataType::Float64 => PolarsDataType::Float64,
             DataType::Int8 => PolarsDataType::Int8,
             DataType::Int16 => PolarsDataType::Int16,
             DataType::Int32 => PolarsDataType::Int32,
             DataType::Int64 => PolarsDataType::Int64,
+            DataType::List(dtype) => PolarsDataType::List(Box::new(dtype.into_polars())),
+            DataType::Time => PolarsDataType::Time,
             DataType::UInt8 => PolarsDataType::UInt8,
             DataType::UInt16 => PolarsDataType::UInt16,
             DataType::UInt32 => PolarsDataType::UInt32,
             DataType::UInt64 => PolarsDataType::UInt64,
-            DataType::Utf8 => PolarsDataType::Utf8,
-            _ => todo!()
+            DataType::Utf8 => PolarsDataType::Utf8
         }
     }
 }
@@ -53,18 +73,43 @@ impl TryFrom<PolarsDataType> for DataType {
         match dtype {
             PolarsDataType::Boolean => Ok(DataType::Boolean),
             PolarsDataType::Date => Ok(DataType::Date),
+            PolarsDataType::Datetime(_, _) => Ok(DataType::DateTime),
+            PolarsDataType::Duration(_) => Ok(DataType::Duration),
             PolarsDataType::Float32 => Ok(DataType::Float32),
             PolarsDataType::Float64 => Ok(DataType::Float64),
             PolarsDataType::Int8 => Ok(DataType::Int8),
             PolarsDataType::Int16 => Ok(DataType::Int16),
             PolarsDataType::Int32 => Ok(DataType::Int32),
             PolarsDataType::Int64 => Ok(DataType::Int64),
+            PolarsDataType::List(dtype) => Ok(DataType::List(Box::new(DataType::try_from(*dtype)?))),
+            PolarsDataType::Time => Ok(DataType::Time),
             PolarsDataType::UInt8 => Ok(DataType::UInt8),
             PolarsDataType::UInt16 => Ok(DataType::UInt16),
             PolarsDataType::UInt32 => Ok(DataType::UInt32),
             PolarsDataType::UInt64 => Ok(DataType::UInt64),
             PolarsDataType::Utf8 => Ok(DataType::Utf8),
-            _ => todo!()
+            // E.g. structs, categoricals and null columns read from Parquet,
+            // IPC or NDJSON files.
+            dtype => Err(PoldaError::QueryError(format!("Unsupported data type {}", dtype)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use polars::datatypes::Field;
+    use super::*;
+
+    #[test]
+    fn unsupported_polars_types() {
+        let dtypes = vec![
+            PolarsDataType::Null,
+            PolarsDataType::Categorical(None),
+            PolarsDataType::Struct(vec![Field::new("a", PolarsDataType::Int64)]),
+            PolarsDataType::List(Box::new(PolarsDataType::Null))
+        ];
+        for dtype in dtypes.into_iter() {
+            assert!(matches!(DataType::try_from(dtype), Err(PoldaError::QueryError(_))));
         }
     }
 }
@@ -5,13 +5,33 @@ use serde::Deserialize;
 use serde::Serialize;
 use std::collections::HashMap;
 use std::collections::HashSet;
-
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+
+use crate::cache::ResultCache;
+use crate::cache::hash_nodes;
+use crate::cache::hash_with;
+use crate::context::ExecutionContext;
 use crate::error::PoldaError;
+use crate::export::ExportFormat;
+use crate::export::write_frame;
+use crate::export::write_parquet;
+use crate::query::PolarsQuery;
+use crate::query::Dialect;
 use crate::query::Query;
+use crate::query::Schema;
+use crate::query::compile_sql;
 
 mod node;
 mod operation;
 mod types;
+mod validate;
+mod view;
 
 pub use node::Node;
 pub use operation::Operation;
@@ -21,16 +41,26 @@ pub use types::aggregate::Aggregate;
 pub use types::aggregate::AggregateComputation;
 pub use types::case::Case;
 pub use types::compute::ComputeOperation;
+pub use types::csv::CsvEncoding;
+pub use types::csv::CsvOptions;
 pub use types::filter::FilterPredicate;
 pub use types::join::JoinType;
 pub use types::join::JoinColumn;
 pub use types::select::SelectColumn;
 pub use types::sort::Sorter;
 pub use types::sort::SortDirection;
+pub use types::sink::ParquetCompression;
+pub use types::sink::WriteMode;
+use types::sink::check_sink_filename;
 pub use types::InputName;
 pub use types::InputPort;
 pub use types::Position;
 pub use types::Value;
+pub use validate::Diagnostic;
+pub use validate::Severity;
+pub use validate::validate;
+pub use view::Page;
+pub use view::View;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Doc {
@@ -39,8 +69,89 @@ pub struct Doc {
 }
 
 impl Doc {
-    pub fn collect(&self, id: &String, limit: Option<usize>) -> Result<DataFrame, PoldaError> {
-        collect(&self.nodes, id, limit)
+    pub fn collect(
+        &self,
+        id: &String,
+        limit: Option<usize>,
+        context: &ExecutionContext
+    ) -> Result<DataFrame, PoldaError> {
+        collect(&self.nodes, id, limit, context)
+    }
+
+    /// Compile a node and it's dependencies into a standalone SQL query.
+    pub fn compile_sql(
+        &self,
+        id: &String,
+        dialect: Dialect,
+        context: &ExecutionContext
+    ) -> Result<String, PoldaError> {
+        let nodes = self.extract_nodes(id)?;
+        compile_sql(&nodes, id, dialect, context)
+    }
+
+    /// Infer the output schema of every node without running any query.
+    pub fn schemas(
+        &self,
+        context: &ExecutionContext
+    ) -> HashMap<String, Result<Schema, PoldaError>> {
+        infer_schemas(&self.nodes, context)
+    }
+
+    /// List the problems of every node.
+    pub fn validate(&self, context: &ExecutionContext) -> Vec<Diagnostic> {
+        let schemas = self.schemas(context);
+        self.validate_schemas(&schemas)
+    }
+
+    /// Same as `validate` but reuse schemas from `Doc::schemas`.
+    pub fn validate_schemas(
+        &self,
+        schemas: &HashMap<String, Result<Schema, PoldaError>>
+    ) -> Vec<Diagnostic> {
+        validate(&self.nodes, &self.index, schemas)
+    }
+
+    /// Content hashes of the nodes, see `hash_nodes`.
+    pub fn hashes(&self, context: &ExecutionContext) -> HashMap<String, u64> {
+        hash_nodes(&self.nodes, context)
+    }
+
+    pub fn has_node(&self, id: &str) -> bool {
+        self.nodes.contains_key(id)
+    }
+
+    /// Get the nodes and every node downstream of them.
+    pub fn dependents(&self, ids: &[String]) -> HashSet<String> {
+        let mut dependents = HashSet::new();
+        let mut ids = ids.to_vec();
+        while let Some(id) = ids.pop() {
+            if let Some(node) = self.nodes.get(&id) {
+                ids.extend(node.outputs().iter().filter(|id| !dependents.contains(*id)).cloned());
+            }
+            dependents.insert(id);
+        }
+        dependents
+    }
+
+    /// Get the source nodes that read one of `paths`.  The paths must be
+    /// canonical.
+    pub fn sources_reading(&self, paths: &HashSet<PathBuf>, context: &ExecutionContext) -> Vec<String> {
+        let root = context.project_dir().canonicalize().ok();
+        self.nodes
+            .values()
+            .filter(|node| match node.source_filename() {
+                Some(filename) => {
+                    // A deleted file can't be resolved anymore.
+                    let path = context
+                        .resolve(filename)
+                        .ok()
+                        .or_else(|| root.as_ref().map(|root| root.join(filename)));
+                    path.is_some_and(|path| paths.contains(&path))
+                }
+                None => false
+            })
+            .map(|node| node.id().clone())
+            .collect()
     }
 
     /// Get a node and it's dependecies.
@@ -415,9 +526,39 @@ impl Doc {
                             id: _,
                             position: _,
                             filename: _,
+                            options: _,
                             outputs: _
                         } => Err(PoldaError::OperationError(format!("Load Csv node doesn't take an input"))),
 
+                        LoadDuckDb {
+                            id: _,
+                            position: _,
+                            filename: _,
+                            table: _,
+                            outputs: _
+                        } => Err(PoldaError::OperationError(format!("Load DuckDB node doesn't take an input"))),
+
+                        LoadIpc {
+                            id: _,
+                            position: _,
+                            filename: _,
+                            outputs: _
+                        } => Err(PoldaError::OperationError(format!("Load IPC node doesn't take an input"))),
+
+                        LoadJson {
+                            id: _,
+                            position: _,
+                            filename: _,
+                            outputs: _
+                        } => Err(PoldaError::OperationError(format!("Load JSON node doesn't take an input"))),
+
+                        LoadParquet {
+                            id: _,
+                            position: _,
+                            filename: _,
+                            outputs: _
+                        } => Err(PoldaError::OperationError(format!("Load Parquet node doesn't take an input"))),
+
                         Select {
                             id: _,
                             position: _,
@@ -499,6 +640,57 @@ impl Doc {
                                 Ok(Some(undo))
                             }
                         }
+
+                        WriteCsv {
+                            id: _,
+                            position: _,
+                            input,
+                            filename: _,
+                            mode: _,
+                            outputs: _
+                        } => {
+                            if let InputName::Primary = &name {
+                                if &new_input != input {
+                                    insert_output = new_input.clone();
+                                    remove_output = input.clone();
+                                }
+                                let undo = Operation::SetInput {
+                                    id: id.clone(),
+                                    name,
+                                    input: input.clone()
+                                };
+                                *input = new_input.clone();
+                                Ok(Some(undo))
+                            } else {
+                                Err(PoldaError::OperationError(format!("Write CSV node doesn't take a secondary input")))
+                            }
+                        }
+
+                        WriteParquet {
+                            id: _,
+                            position: _,
+                            input,
+                            filename: _,
+                            mode: _,
+                            compression: _,
+                            outputs: _
+                        } => {
+                            if let InputName::Primary = &name {
+                                if &new_input != input {
+                                    insert_output = new_input.clone();
+                                    remove_output = input.clone();
+                                }
+                                let undo = Operation::SetInput {
+                                    id: id.clone(),
+                                    name,
+                                    input: input.clone()
+                                };
+                                *input = new_input.clone();
+                                Ok(Some(undo))
+                            } else {
+                                Err(PoldaError::OperationError(format!("Write Parquet node doesn't take a secondary input")))
+                            }
+                        }
                     }
                 } else {
                     Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
@@ -611,6 +803,7 @@ impl Doc {
                             id: _,
                             position,
                             filename: _,
+                            options: _,
                             outputs: _
                         } => {
                             let undo = Operation::SetPosition {
@@ -621,6 +814,35 @@ impl Doc {
                             Ok(Some(undo))
                         }
 
+                        LoadDuckDb {
+                            id: _,
+                            position,
+                            filename: _,
+                            table: _,
+                            outputs: _
+                        } => set_position!(id, position, new_position),
+
+                        LoadIpc {
+                            id: _,
+                            position,
+                            filename: _,
+                            outputs: _
+                        } => set_position!(id, position, new_position),
+
+                        LoadJson {
+                            id: _,
+                            position,
+                            filename: _,
+                            outputs: _
+                        } => set_position!(id, position, new_position),
+
+                        LoadParquet {
+                            id: _,
+                            position,
+                            filename: _,
+                            outputs: _
+                        } => set_position!(id, position, new_position),
+
                         Select {
                             id: _,
                             position,
@@ -644,6 +866,25 @@ impl Doc {
                             secondary_input: _,
                             outputs: _
                         } => set_position!(id, position, new_position),
+
+                        WriteCsv {
+                            id: _,
+                            position,
+                            input: _,
+                            filename: _,
+                            mode: _,
+                            outputs: _
+                        } => set_position!(id, position, new_position),
+
+                        WriteParquet {
+                            id: _,
+                            position,
+                            input: _,
+                            filename: _,
+                            mode: _,
+                            compression: _,
+                            outputs: _
+                        } => set_position!(id, position, new_position),
                     }
                 } else {
                     Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
@@ -1318,6 +1559,7 @@ impl Doc {
                         id: _,
                         position: _,
                         filename,
+                        options: _,
                         outputs: _
                     } = node {
                         let undo = SetLoadCsvFilename {
@@ -1334,196 +1576,597 @@ impl Doc {
                 }
             }
 
-            SetJoinType { id, join_type: new_join_type } => {
+            SetLoadCsvDelimiter { id, delimiter: new_delimiter } => {
                 if let Some(node) = self.nodes.get_mut(&id) {
-                    if let Node::Join {
+                    if let Node::LoadCsv {
                         id: _,
                         position: _,
-                        left_input: _,
-                        right_input: _,
-                        join_type,
-                        columns: _,
+                        filename: _,
+                        options,
                         outputs: _
                     } = node {
-                        let undo = SetJoinType {
+                        let undo = SetLoadCsvDelimiter {
                             id,
-                            join_type: join_type.clone()
+                            delimiter: options.delimiter
                         };
-                        *join_type = new_join_type;
+                        options.delimiter = new_delimiter;
                         Ok(Some(undo))
                     } else {
-                        Err(PoldaError::OperationError(format!("Can't set join type to a non-join node")))
+                        Err(PoldaError::OperationError(format!("Can't set csv delimiter to a non-load-csv node")))
                     }
                 } else {
                     Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
                 }
             }
 
-            InsertJoinColumn { id, index, join_column } => {
+            SetLoadCsvHasHeader { id, has_header: new_has_header } => {
                 if let Some(node) = self.nodes.get_mut(&id) {
-                    if let Node::Join {
+                    if let Node::LoadCsv {
                         id: _,
                         position: _,
-                        left_input: _,
-                        right_input: _,
-                        join_type: _,
-                        columns,
+                        filename: _,
+                        options,
                         outputs: _
                     } = node {
-                        if index <= columns.len() {
-                            columns.splice(index..index, [join_column]);
-                            let undo = Operation::DeleteJoinColumn {
-                                id,
-                                index
-                            };
-                            Ok(Some(undo))
-                        } else {
-                            Err(PoldaError::OperationError(format!("Can't insert a new join column at index {}. Possible index (0 - {})", index, columns.len())))
-                        }
+                        let undo = SetLoadCsvHasHeader {
+                            id,
+                            has_header: options.has_header
+                        };
+                        options.has_header = new_has_header;
+                        Ok(Some(undo))
                     } else {
-                        Err(PoldaError::OperationError(format!("Can't insert a join column into a non-join node")))
+                        Err(PoldaError::OperationError(format!("Can't set csv header to a non-load-csv node")))
                     }
                 } else {
                     Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
                 }
             }
 
-            DeleteJoinColumn { id, index } => {
+            SetLoadCsvQuoteChar { id, quote_char: new_quote_char } => {
                 if let Some(node) = self.nodes.get_mut(&id) {
-                    if let Node::Join {
+                    if let Node::LoadCsv {
                         id: _,
                         position: _,
-                        left_input: _,
-                        right_input: _,
-                        join_type: _,
-                        columns,
+                        filename: _,
+                        options,
                         outputs: _
                     } = node {
-                        if index < columns.len() {
-                            let undo = InsertJoinColumn {
-                                id,
-                                index,
-                                join_column: columns[index].clone()
-                            };
-                            let end = index + 1;
-                            columns.splice(index..end, []);
-                            Ok(Some(undo))
-                        } else {
-                            Err(PoldaError::OperationError(format!("There's no join column at index {}. Possible index (0 - {})", index, columns.len())))
-                        }
+                        let undo = SetLoadCsvQuoteChar {
+                            id,
+                            quote_char: options.quote_char
+                        };
+                        options.quote_char = new_quote_char;
+                        Ok(Some(undo))
                     } else {
-                        Err(PoldaError::OperationError(format!("Can't delete a join column from a non-join node")))
+                        Err(PoldaError::OperationError(format!("Can't set csv quote char to a non-load-csv node")))
                     }
                 } else {
                     Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
                 }
             }
 
-            SetJoinColumnLeft { id, index, column } => {
+            SetLoadCsvNullValues { id, null_values: new_null_values } => {
                 if let Some(node) = self.nodes.get_mut(&id) {
-                    if let Node::Join {
+                    if let Node::LoadCsv {
                         id: _,
                         position: _,
-                        left_input: _,
-                        right_input: _,
-                        join_type: _,
-                        columns,
+                        filename: _,
+                        options,
                         outputs: _
                     } = node {
-                        if index < columns.len() {
-                            let undo = SetJoinColumnLeft {
-                                id,
-                                index,
-                                column: columns[index].left.clone()
-                            };
-                            columns[index].left = column;
-                            Ok(Some(undo))
-                        } else {
-                            Err(PoldaError::OperationError(format!("There's no join column at index {}. Possible index (0 - {})", index, columns.len())))
-                        }
+                        let undo = SetLoadCsvNullValues {
+                            id,
+                            null_values: options.null_values.clone()
+                        };
+                        options.null_values = new_null_values;
+                        Ok(Some(undo))
                     } else {
-                        Err(PoldaError::OperationError(format!("Can't set left join column to a non-join node")))
+                        Err(PoldaError::OperationError(format!("Can't set csv null values to a non-load-csv node")))
                     }
                 } else {
                     Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
                 }
             }
 
-            SetJoinColumnRight { id, index, column } => {
+            SetLoadCsvSkipRows { id, skip_rows: new_skip_rows } => {
                 if let Some(node) = self.nodes.get_mut(&id) {
-                    if let Node::Join {
+                    if let Node::LoadCsv {
                         id: _,
                         position: _,
-                        left_input: _,
-                        right_input: _,
-                        join_type: _,
-                        columns,
+                        filename: _,
+                        options,
                         outputs: _
                     } = node {
-                        if index < columns.len() {
-                            let undo = SetJoinColumnRight {
-                                id,
-                                index,
-                                column: columns[index].right.clone()
-                            };
-                            columns[index].right = column;
-                            Ok(Some(undo))
-                        } else {
-                            Err(PoldaError::OperationError(format!("There's no join column at index {}. Possible index (0 - {})", index, columns.len())))
-                        }
+                        let undo = SetLoadCsvSkipRows {
+                            id,
+                            skip_rows: options.skip_rows
+                        };
+                        options.skip_rows = new_skip_rows;
+                        Ok(Some(undo))
                     } else {
-                        Err(PoldaError::OperationError(format!("Can't set right join column to a non-join node")))
+                        Err(PoldaError::OperationError(format!("Can't set csv skip rows to a non-load-csv node")))
                     }
                 } else {
                     Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
                 }
             }
 
-            InsertSelect { id, index, column } => {
+            SetLoadCsvEncoding { id, encoding: new_encoding } => {
                 if let Some(node) = self.nodes.get_mut(&id) {
-                    if let Node::Select {
+                    if let Node::LoadCsv {
                         id: _,
                         position: _,
-                        input: _,
-                        columns,
+                        filename: _,
+                        options,
                         outputs: _
                     } = node {
-                        if index <= columns.len() {
-                            columns.splice(index..index, [column]);
-                            let undo = Operation::DeleteSelect {
-                                id,
-                                index
-                            };
-                            Ok(Some(undo))
-                        } else {
-                            Err(PoldaError::OperationError(format!("Can't insert a new select column at index {}. Possible index (0 - {})", index, columns.len())))
-                        }
+                        let undo = SetLoadCsvEncoding {
+                            id,
+                            encoding: options.encoding
+                        };
+                        options.encoding = new_encoding;
+                        Ok(Some(undo))
                     } else {
-                        Err(PoldaError::OperationError(format!("Can't insert a select column into a non-select node")))
+                        Err(PoldaError::OperationError(format!("Can't set csv encoding to a non-load-csv node")))
                     }
                 } else {
                     Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
                 }
             }
 
-            DeleteSelect { id, index } => {
+            SetLoadCsvInferSchemaLength { id, infer_schema_length: new_infer_schema_length } => {
                 if let Some(node) = self.nodes.get_mut(&id) {
-                    if let Node::Select {
+                    if let Node::LoadCsv {
                         id: _,
                         position: _,
-                        input: _,
-                        columns,
+                        filename: _,
+                        options,
                         outputs: _
                     } = node {
-                        if index < columns.len() {
-                            let undo = InsertSelect {
-                                id,
-                                index,
-                                column: columns[index].clone()
-                            };
-                            let end = index + 1;
-                            columns.splice(index..end, []);
-                            Ok(Some(undo))
+                        let undo = SetLoadCsvInferSchemaLength {
+                            id,
+                            infer_schema_length: options.infer_schema_length
+                        };
+                        options.infer_schema_length = new_infer_schema_length;
+                        Ok(Some(undo))
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set csv infer schema length to a non-load-csv node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            SetLoadCsvColumnType { id, column, data_type: new_data_type } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::LoadCsv {
+                        id: _,
+                        position: _,
+                        filename: _,
+                        options,
+                        outputs: _
+                    } = node {
+                        let data_type = match new_data_type {
+                            Some(data_type) => options.column_types.insert(column.clone(), data_type),
+                            None => options.column_types.remove(&column)
+                        };
+                        let undo = SetLoadCsvColumnType { id, column, data_type };
+                        Ok(Some(undo))
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set csv column type to a non-load-csv node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            // LoadDuckDb node operations
+
+            SetLoadDuckDbFilename { id, filename: new_filename } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::LoadDuckDb {
+                        id: _,
+                        position: _,
+                        filename,
+                        table: _,
+                        outputs: _
+                    } = node {
+                        let undo = SetLoadDuckDbFilename { id, filename: filename.clone() };
+                        *filename = new_filename;
+                        Ok(Some(undo))
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set duck db filename to a non-load-duck-db node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            SetLoadDuckDbTable { id, table: new_table } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::LoadDuckDb {
+                        id: _,
+                        position: _,
+                        filename: _,
+                        table,
+                        outputs: _
+                    } = node {
+                        let undo = SetLoadDuckDbTable { id, table: table.clone() };
+                        *table = new_table;
+                        Ok(Some(undo))
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set duck db table to a non-load-duck-db node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            // LoadIpc node operations
+
+            SetLoadIpcFilename { id, filename: new_filename } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::LoadIpc {
+                        id: _,
+                        position: _,
+                        filename,
+                        outputs: _
+                    } = node {
+                        let undo = SetLoadIpcFilename { id, filename: filename.clone() };
+                        *filename = new_filename;
+                        Ok(Some(undo))
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set ipc filename to a non-load-ipc node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            // LoadJson node operations
+
+            SetLoadJsonFilename { id, filename: new_filename } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::LoadJson {
+                        id: _,
+                        position: _,
+                        filename,
+                        outputs: _
+                    } = node {
+                        let undo = SetLoadJsonFilename { id, filename: filename.clone() };
+                        *filename = new_filename;
+                        Ok(Some(undo))
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set json filename to a non-load-json node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            // LoadParquet node operations
+
+            SetLoadParquetFilename { id, filename: new_filename } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::LoadParquet {
+                        id: _,
+                        position: _,
+                        filename,
+                        outputs: _
+                    } = node {
+                        let undo = SetLoadParquetFilename { id, filename: filename.clone() };
+                        *filename = new_filename;
+                        Ok(Some(undo))
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set parquet filename to a non-load-parquet node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            // WriteCsv node operations
+
+            SetWriteCsvFilename { id, filename: new_filename } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::WriteCsv {
+                        id: _,
+                        position: _,
+                        input: _,
+                        filename,
+                        mode: _,
+                        outputs: _
+                    } = node {
+                        let undo = SetWriteCsvFilename { id, filename: filename.clone() };
+                        *filename = new_filename;
+                        Ok(Some(undo))
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set csv filename to a non-write-csv node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            SetWriteCsvMode { id, mode: new_mode } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::WriteCsv {
+                        id: _,
+                        position: _,
+                        input: _,
+                        filename: _,
+                        mode,
+                        outputs: _
+                    } = node {
+                        let undo = SetWriteCsvMode { id, mode: *mode };
+                        *mode = new_mode;
+                        Ok(Some(undo))
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set write mode to a non-write-csv node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            // WriteParquet node operations
+
+            SetWriteParquetFilename { id, filename: new_filename } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::WriteParquet {
+                        id: _,
+                        position: _,
+                        input: _,
+                        filename,
+                        mode: _,
+                        compression: _,
+                        outputs: _
+                    } = node {
+                        let undo = SetWriteParquetFilename { id, filename: filename.clone() };
+                        *filename = new_filename;
+                        Ok(Some(undo))
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set parquet filename to a non-write-parquet node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            SetWriteParquetMode { id, mode: new_mode } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::WriteParquet {
+                        id: _,
+                        position: _,
+                        input: _,
+                        filename: _,
+                        mode,
+                        compression: _,
+                        outputs: _
+                    } = node {
+                        let undo = SetWriteParquetMode { id, mode: *mode };
+                        *mode = new_mode;
+                        Ok(Some(undo))
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set write mode to a non-write-parquet node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            SetWriteParquetCompression { id, compression: new_compression } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::WriteParquet {
+                        id: _,
+                        position: _,
+                        input: _,
+                        filename: _,
+                        mode: _,
+                        compression,
+                        outputs: _
+                    } = node {
+                        let undo = SetWriteParquetCompression { id, compression: *compression };
+                        *compression = new_compression;
+                        Ok(Some(undo))
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set parquet compression to a non-write-parquet node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            SetJoinType { id, join_type: new_join_type } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::Join {
+                        id: _,
+                        position: _,
+                        left_input: _,
+                        right_input: _,
+                        join_type,
+                        columns: _,
+                        outputs: _
+                    } = node {
+                        let undo = SetJoinType {
+                            id,
+                            join_type: join_type.clone()
+                        };
+                        *join_type = new_join_type;
+                        Ok(Some(undo))
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set join type to a non-join node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            InsertJoinColumn { id, index, join_column } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::Join {
+                        id: _,
+                        position: _,
+                        left_input: _,
+                        right_input: _,
+                        join_type: _,
+                        columns,
+                        outputs: _
+                    } = node {
+                        if index <= columns.len() {
+                            columns.splice(index..index, [join_column]);
+                            let undo = Operation::DeleteJoinColumn {
+                                id,
+                                index
+                            };
+                            Ok(Some(undo))
+                        } else {
+                            Err(PoldaError::OperationError(format!("Can't insert a new join column at index {}. Possible index (0 - {})", index, columns.len())))
+                        }
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't insert a join column into a non-join node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            DeleteJoinColumn { id, index } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::Join {
+                        id: _,
+                        position: _,
+                        left_input: _,
+                        right_input: _,
+                        join_type: _,
+                        columns,
+                        outputs: _
+                    } = node {
+                        if index < columns.len() {
+                            let undo = InsertJoinColumn {
+                                id,
+                                index,
+                                join_column: columns[index].clone()
+                            };
+                            let end = index + 1;
+                            columns.splice(index..end, []);
+                            Ok(Some(undo))
+                        } else {
+                            Err(PoldaError::OperationError(format!("There's no join column at index {}. Possible index (0 - {})", index, columns.len())))
+                        }
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't delete a join column from a non-join node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            SetJoinColumnLeft { id, index, column } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::Join {
+                        id: _,
+                        position: _,
+                        left_input: _,
+                        right_input: _,
+                        join_type: _,
+                        columns,
+                        outputs: _
+                    } = node {
+                        if index < columns.len() {
+                            let undo = SetJoinColumnLeft {
+                                id,
+                                index,
+                                column: columns[index].left.clone()
+                            };
+                            columns[index].left = column;
+                            Ok(Some(undo))
+                        } else {
+                            Err(PoldaError::OperationError(format!("There's no join column at index {}. Possible index (0 - {})", index, columns.len())))
+                        }
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set left join column to a non-join node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            SetJoinColumnRight { id, index, column } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::Join {
+                        id: _,
+                        position: _,
+                        left_input: _,
+                        right_input: _,
+                        join_type: _,
+                        columns,
+                        outputs: _
+                    } = node {
+                        if index < columns.len() {
+                            let undo = SetJoinColumnRight {
+                                id,
+                                index,
+                                column: columns[index].right.clone()
+                            };
+                            columns[index].right = column;
+                            Ok(Some(undo))
+                        } else {
+                            Err(PoldaError::OperationError(format!("There's no join column at index {}. Possible index (0 - {})", index, columns.len())))
+                        }
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't set right join column to a non-join node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            InsertSelect { id, index, column } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::Select {
+                        id: _,
+                        position: _,
+                        input: _,
+                        columns,
+                        outputs: _
+                    } = node {
+                        if index <= columns.len() {
+                            columns.splice(index..index, [column]);
+                            let undo = Operation::DeleteSelect {
+                                id,
+                                index
+                            };
+                            Ok(Some(undo))
+                        } else {
+                            Err(PoldaError::OperationError(format!("Can't insert a new select column at index {}. Possible index (0 - {})", index, columns.len())))
+                        }
+                    } else {
+                        Err(PoldaError::OperationError(format!("Can't insert a select column into a non-select node")))
+                    }
+                } else {
+                    Err(PoldaError::OperationError(format!("Node with id {} doesn't exist", id)))
+                }
+            }
+
+            DeleteSelect { id, index } => {
+                if let Some(node) = self.nodes.get_mut(&id) {
+                    if let Node::Select {
+                        id: _,
+                        position: _,
+                        input: _,
+                        columns,
+                        outputs: _
+                    } = node {
+                        if index < columns.len() {
+                            let undo = InsertSelect {
+                                id,
+                                index,
+                                column: columns[index].clone()
+                            };
+                            let end = index + 1;
+                            columns.splice(index..end, []);
+                            Ok(Some(undo))
                         } else {
                             Err(PoldaError::OperationError(format!("There's no select column at index {}. Possible index (0 - {})", index, columns.len())))
                         }
@@ -1716,13 +2359,189 @@ impl Doc {
 pub fn collect(
     nodes: &HashMap<String, Node>,
     id: &String,
-    limit: Option<usize>
+    limit: Option<usize>,
+    context: &ExecutionContext
+) -> Result<DataFrame, PoldaError> {
+    let df = build_query(nodes, id, context, &mut |_| None)?.collect()?;
+    Ok(df.head(limit))
+}
+
+/// Same as `collect` but the result is stored in `cache`, and cached results
+/// of upstream nodes are reused instead of being recomputed.
+pub fn collect_cached(
+    nodes: &HashMap<String, Node>,
+    id: &String,
+    limit: Option<usize>,
+    context: &ExecutionContext,
+    cache: &Mutex<ResultCache>
 ) -> Result<DataFrame, PoldaError> {
+    let (df, _) = collect_result(nodes, id, context, cache)?;
+    Ok(df.head(limit))
+}
+
+/// Collect a page of the result of a node.  The whole (sorted) result is
+/// cached, so requesting the next page doesn't rerun the query.
+pub fn collect_page(
+    nodes: &HashMap<String, Node>,
+    id: &String,
+    view: &View,
+    context: &ExecutionContext,
+    cache: &Mutex<ResultCache>
+) -> Result<Page, PoldaError> {
+    let (df, key) = collect_result(nodes, id, context, cache)?;
+    if view.sorters.is_empty() {
+        return Ok(view::page(&df, view));
+    }
+
+    let key = key.map(|key| hash_with(key, &view.sorters));
+    let cached_df = key.and_then(|key| cache.lock().unwrap().get(key));
+    let df = if let Some(df) = cached_df {
+        df
+    } else {
+        let df = view::sort_frame(df, &view.sorters)?;
+        if let Some(key) = key {
+            cache.lock().unwrap().insert(key, df.clone());
+        }
+        df
+    };
+    Ok(view::page(&df, view))
+}
+
+static SINK_TMP_IDS: AtomicUsize = AtomicUsize::new(0);
+
+/// Run a sink node: write the result of its input to the target file and
+/// return the number of written rows.  The file is written next to the
+/// target first, so a failed run doesn't leave a truncated file behind.
+/// Concurrent runs of the same target each use their own hidden file.
+pub fn materialize(
+    nodes: &HashMap<String, Node>,
+    id: &String,
+    context: &ExecutionContext,
+    cache: &Mutex<ResultCache>
+) -> Result<usize, PoldaError> {
+    let (filename, mode, extension) = match nodes.get(id) {
+        Some(Node::WriteCsv {
+            id: _,
+            position: _,
+            input: _,
+            filename,
+            mode,
+            outputs: _
+        }) => (filename, mode, "csv"),
+
+        Some(Node::WriteParquet {
+            id: _,
+            position: _,
+            input: _,
+            filename,
+            mode,
+            compression: _,
+            outputs: _
+        }) => (filename, mode, "parquet"),
+
+        Some(_) => return Err(PoldaError::QueryError(format!("Node {} isn't a sink node", id))),
+        None => return Err(PoldaError::QueryError(format!("Node {} doesn't exist", id)))
+    };
+
+    check_sink_filename(filename, extension).map_err(PoldaError::QueryError)?;
+    let path = context.resolve_target(filename)?;
+    if *mode == WriteMode::ErrorIfExists && path.exists() {
+        return Err(PoldaError::QueryError(format!("File \"{}\" already exists", filename)));
+    }
+
+    let (mut df, _) = collect_result(nodes, id, context, cache)?;
+    context.check_canceled()?;
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp_path = path.with_file_name(format!(
+        ".{}.{}.tmp",
+        name,
+        SINK_TMP_IDS.fetch_add(1, Ordering::Relaxed)
+    ));
+    let res = File::create(&tmp_path)
+        .map_err(PoldaError::from)
+        .and_then(|file| match nodes.get(id) {
+            Some(Node::WriteParquet {
+                id: _,
+                position: _,
+                input: _,
+                filename: _,
+                mode: _,
+                compression,
+                outputs: _
+            }) => write_parquet(file, &mut df, *compression),
+            _ => write_frame(file, &mut df, ExportFormat::Csv)
+        })
+        .and_then(|_| match mode {
+            WriteMode::Overwrite => Ok(fs::rename(&tmp_path, &path)?),
+            // The target may have been created while the query ran, so link
+            // the file instead of renaming it, which never replaces the target.
+            WriteMode::ErrorIfExists => match fs::hard_link(&tmp_path, &path) {
+                Ok(()) => Ok(fs::remove_file(&tmp_path)?),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    Err(PoldaError::QueryError(format!("File \"{}\" already exists", filename)))
+                }
+                Err(e) => Err(e.into())
+            }
+        });
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    res?;
+
+    Ok(df.height())
+}
+
+/// Collect the whole result of a node and return its cache key.
+fn collect_result(
+    nodes: &HashMap<String, Node>,
+    id: &String,
+    context: &ExecutionContext,
+    cache: &Mutex<ResultCache>
+) -> Result<(DataFrame, Option<u64>), PoldaError> {
+    let hashes = hash_nodes(nodes, context);
+    let df = if let Some(key) = hashes.get(id) {
+        // Don't hold the lock while the query runs.
+        let cached_df = cache.lock().unwrap().get(*key);
+        if let Some(df) = cached_df {
+            df
+        } else {
+            let mut cached = |id: &String| {
+                hashes.get(id).and_then(|key| cache.lock().unwrap().get(*key))
+            };
+            let df = build_query(nodes, id, context, &mut cached)?.collect()?;
+            // The result is still valid if the query has been canceled in
+            // the meantime.
+            cache.lock().unwrap().insert(*key, df.clone());
+            context.check_canceled()?;
+            df
+        }
+    } else {
+        build_query(nodes, id, context, &mut |_| None)?.collect()?
+    };
+    Ok((df, hashes.get(id).copied()))
+}
+
+/// Build the query of a node.  `cached` returns the result of a node that
+/// doesn't have to be recomputed.
+fn build_query(
+    nodes: &HashMap<String, Node>,
+    id: &String,
+    context: &ExecutionContext,
+    cached: &mut dyn FnMut(&String) -> Option<DataFrame>
+) -> Result<Query, PoldaError> {
     let mut queries: HashMap<String, Query> = HashMap::new();
     let mut polars_queries: HashMap<String, Query> = HashMap::new();
     let mut nodes_to_query = vec![id.clone()];
 
     while let Some(id) = nodes_to_query.last().cloned() {
+        context.check_canceled()?;
+        if let Some(df) = cached(&id) {
+            let query = PolarsQuery::try_from_frame(df)?;
+            queries.insert(id.clone(), Query::Polars(query));
+            nodes_to_query.pop();
+            continue;
+        }
         if let Some(node) = nodes.get(&id) {
             let inputs = node.inputs();
             let connected_inputs = inputs
@@ -1799,7 +2618,7 @@ pub fn collect(
                     .collect()
             };
 
-            let query = Query::from_node(node, input_queries)?;
+            let query = Query::from_node(node, input_queries, context)?;
             queries.insert(id.clone(), query);
             nodes_to_query.pop();
         } else {
@@ -1809,14 +2628,78 @@ pub fn collect(
         }
     }
 
-    let df = queries.remove(id).unwrap().collect()?;
+    Ok(queries.remove(id).unwrap())
+}
+
+/// Walk the graph in topological order and validate every node.  A node
+/// whose input is invalid is invalid too.
+pub fn infer_schemas(
+    nodes: &HashMap<String, Node>,
+    context: &ExecutionContext
+) -> HashMap<String, Result<Schema, PoldaError>> {
+    let mut schemas: HashMap<String, Result<Schema, PoldaError>> = HashMap::new();
+
+    for id in nodes.keys() {
+        let mut nodes_to_infer = vec![id.clone()];
 
-    Ok(df.head(limit))
+        while let Some(id) = nodes_to_infer.last().cloned() {
+            if schemas.contains_key(&id) {
+                nodes_to_infer.pop();
+                continue;
+            }
+            // Only existing nodes are pushed.
+            let node = nodes.get(&id).unwrap();
+
+            let mut result = None;
+            let mut are_inputs_inferred = true;
+            for input in node.inputs().into_iter().flatten() {
+                if schemas.contains_key(input) {
+                    continue;
+                }
+                if !nodes.contains_key(input) {
+                    result = Some(Err(PoldaError::DocError(format!("Node with id \"{}\" doesn't exist", input))));
+                    break;
+                }
+                if nodes_to_infer.contains(input) {
+                    result = Some(Err(PoldaError::DocError(format!("Node {} is part of a cycle", id))));
+                    break;
+                }
+                are_inputs_inferred = false;
+                nodes_to_infer.push(input.clone());
+            }
+
+            if result.is_none() && !are_inputs_inferred {
+                continue;
+            }
+
+            let result = result.unwrap_or_else(|| {
+                let inputs = node.inputs();
+                let mut input_schemas = Vec::with_capacity(inputs.len());
+                for input in inputs.iter() {
+                    match input.as_ref().map(|input| (input, schemas.get(input).unwrap())) {
+                        Some((_, Ok(schema))) => input_schemas.push(schema.clone()),
+                        Some((input, Err(_))) => {
+                            return Err(PoldaError::QueryError(format!("Input node {} is invalid", input)));
+                        }
+                        None => {
+                            return Err(PoldaError::QueryError(format!("Node {} is missing an input", id)));
+                        }
+                    }
+                }
+                Schema::try_from_node(node, input_schemas, context)
+            });
+            schemas.insert(id, result);
+            nodes_to_infer.pop();
+        }
+    }
+
+    schemas
 }
 
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
+    use crate::data_type::DataType;
     use super::*;
 
     #[test]
@@ -1831,6 +2714,7 @@ mod tests {
                         y: 0.0
                     },
                     filename: "data/supermarket_sales.csv".to_string(),
+                    options: CsvOptions::default(),
                     outputs: HashSet::new()
                 }
             },
@@ -1944,7 +2828,329 @@ mod tests {
         ];
         doc.execute_operations(ops).unwrap();
         println!("{:#?}", doc);
-        let df = doc.collect(&String::from("d"), None).unwrap();
+        let df = doc.collect(&String::from("d"), None, &ExecutionContext::default()).unwrap();
         println!("{:#?}", df);
     }
+
+    #[test]
+    fn set_load_csv_options() {
+        let mut doc = Doc::new();
+        let ops = vec![
+            Operation::InsertNode {
+                node: Node::LoadCsv {
+                    id: "a".to_string(),
+                    position: Position {
+                        x: 0.0,
+                        y: 0.0
+                    },
+                    filename: "data.csv".to_string(),
+                    options: CsvOptions::default(),
+                    outputs: HashSet::new()
+                }
+            },
+            Operation::InsertIndex {
+                id: "a".to_string(),
+                index: 0
+            }
+        ];
+        doc.execute_operations(ops).unwrap();
+
+        let ops = vec![
+            Operation::SetLoadCsvDelimiter {
+                id: "a".to_string(),
+                delimiter: ';'
+            },
+            Operation::SetLoadCsvColumnType {
+                id: "a".to_string(),
+                column: "Total".to_string(),
+                data_type: Some(DataType::Float64)
+            }
+        ];
+        let undo = doc.execute_operations(ops).unwrap();
+        if let Some(Node::LoadCsv { options, .. }) = doc.nodes.get("a") {
+            assert_eq!(options.delimiter, ';');
+            assert_eq!(options.column_types.get("Total"), Some(&DataType::Float64));
+        } else {
+            panic!("LoadCsv node doesn't exist");
+        }
+
+        doc.execute_operations(undo).unwrap();
+        if let Some(Node::LoadCsv { options, .. }) = doc.nodes.get("a") {
+            assert_eq!(options.delimiter, ',');
+            assert!(options.column_types.is_empty());
+        } else {
+            panic!("LoadCsv node doesn't exist");
+        }
+    }
+
+    /// A temporary project directory with the given files.
+    fn project(name: &str, files: &[(&str, &str)]) -> (PathBuf, ExecutionContext) {
+        let dir = std::env::temp_dir().join(format!("polda-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for (filename, content) in files.iter() {
+            std::fs::write(dir.join(filename), content).unwrap();
+        }
+        let context = ExecutionContext::new(dir.clone());
+        (dir, context)
+    }
+
+    fn load_csv(id: &str, filename: &str) -> Node {
+        Node::LoadCsv {
+            id: id.to_string(),
+            position: Position { x: 0.0, y: 0.0 },
+            filename: filename.to_string(),
+            options: CsvOptions::default(),
+            outputs: HashSet::new()
+        }
+    }
+
+    /// "b" is valid, "c" selects a missing column, "d" reads from "c" and
+    /// "e" has no input.
+    fn invalid_doc() -> Doc {
+        let position = Position { x: 0.0, y: 0.0 };
+        let select = |id: &str, column: &str| Node::Select {
+            id: id.to_string(),
+            position: position.clone(),
+            input: None,
+            columns: vec![SelectColumn { column: column.to_string(), alias: "".to_string() }],
+            outputs: HashSet::new()
+        };
+        let set_input = |id: &str, input: &str| Operation::SetInput {
+            id: id.to_string(),
+            name: InputName::Primary,
+            input: Some(input.to_string())
+        };
+        let mut doc = Doc::new();
+        let ops = vec![
+            Operation::InsertNode { node: load_csv("a", "data/supermarket_sales.csv") },
+            Operation::InsertNode { node: select("b", "City") },
+            Operation::InsertNode { node: select("c", "Missing") },
+            Operation::InsertNode { node: select("d", "Missing") },
+            Operation::InsertNode { node: select("e", "City") },
+            set_input("b", "a"),
+            set_input("c", "a"),
+            set_input("d", "c")
+        ];
+        doc.execute_operations(ops).unwrap();
+        doc
+    }
+
+    #[test]
+    fn infer_doc_schemas() {
+        let doc = invalid_doc();
+        let schemas = doc.schemas(&ExecutionContext::default());
+        let a = schemas.get("a").unwrap().as_ref().unwrap();
+        assert_eq!(a.columns()[0].name, "Invoice ID");
+        let b = schemas.get("b").unwrap().as_ref().unwrap();
+        assert_eq!(b.get("City"), Some(&DataType::Utf8));
+        assert!(schemas.get("c").unwrap().is_err());
+        assert!(schemas.get("d").unwrap().is_err());
+        assert!(schemas.get("e").unwrap().is_err());
+    }
+
+    #[test]
+    fn validate_doc() {
+        let doc = invalid_doc();
+        let diagnostics = doc.validate(&ExecutionContext::default());
+        let find = |id: &str| diagnostics.iter().filter(|d| d.node_id == id).collect::<Vec<_>>();
+        assert!(find("a").is_empty());
+        assert!(find("b").is_empty());
+        let c = find("c");
+        assert_eq!(c.len(), 1);
+        assert_eq!((c[0].field.as_deref(), c[0].index, c[0].severity), (Some("columns"), Some(0), Severity::Error));
+        let d = find("d");
+        assert_eq!((d[0].field.as_deref(), d[0].severity), (Some("input"), Severity::Warning));
+        let e = find("e");
+        assert_eq!((e[0].field.as_deref(), e[0].severity), (Some("input"), Severity::Error));
+    }
+
+    #[test]
+    fn bins_edges() {
+        let (dir, context) = project("bins", &[("a.csv", "a\n-0.5\n0\n0.5\n0.9999999999999999\n1\n1.5\n")]);
+        let position = Position { x: 0.0, y: 0.0 };
+        let bins = |upper_bound: f64| Node::Bins {
+            id: "b".to_string(),
+            position: position.clone(),
+            input: None,
+            name: "bin".to_string(),
+            column: "a".to_string(),
+            lower_bound: 0.0,
+            upper_bound,
+            count: 3,
+            outputs: HashSet::new()
+        };
+        let mut doc = Doc::new();
+        let ops = vec![
+            Operation::InsertNode { node: load_csv("a", "a.csv") },
+            Operation::InsertNode { node: bins(1.0) },
+            Operation::SetInput {
+                id: "b".to_string(),
+                name: InputName::Primary,
+                input: Some("a".to_string())
+            }
+        ];
+        doc.execute_operations(ops).unwrap();
+
+        // 0.9999999999999999 / (1 / 3) rounds to 3.
+        let df = doc.collect(&"b".to_string(), None, &context).unwrap();
+        let bins_column: Vec<Option<u32>> = df.column("bin").unwrap().u32().unwrap().into_iter().collect();
+        assert_eq!(bins_column, vec![None, Some(0), Some(1), Some(2), Some(2), None]);
+
+        let input = doc.schemas(&context).remove("a").unwrap().unwrap();
+        assert!(Schema::try_from_node(&bins(f64::INFINITY), vec![input], &context).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn case_without_cases() {
+        let (dir, context) = project("case", &[("a.csv", "x\n1\n2\n")]);
+        let position = Position { x: 0.0, y: 0.0 };
+        let mut doc = Doc::new();
+        let ops = vec![
+            Operation::InsertNode { node: load_csv("a", "a.csv") },
+            Operation::InsertNode {
+                node: Node::Case {
+                    id: "b".to_string(),
+                    position: position.clone(),
+                    input: None,
+                    name: "c".to_string(),
+                    data_type: DataType::Utf8,
+                    cases: vec![],
+                    default: Value::Constant("z".to_string()),
+                    outputs: HashSet::new()
+                }
+            },
+            Operation::SetInput {
+                id: "b".to_string(),
+                name: InputName::Primary,
+                input: Some("a".to_string())
+            }
+        ];
+        doc.execute_operations(ops).unwrap();
+
+        // The column is in the schema, so it's added with the default value.
+        let df = doc.collect(&"b".to_string(), None, &context).unwrap();
+        let c: Vec<Option<&str>> = df.column("c").unwrap().utf8().unwrap().into_iter().collect();
+        assert_eq!(c, vec![Some("z"), Some("z")]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn join_columns() {
+        let (dir, context) = project("join", &[
+            ("a.csv", "id,name\n1,a\n2,b\n"),
+            ("b.csv", "key,name,total\n1,x,10\n3,y,30\n")
+        ]);
+        let mut doc = Doc::new();
+        let ops = vec![
+            Operation::InsertNode { node: load_csv("a", "a.csv") },
+            Operation::InsertNode { node: load_csv("b", "b.csv") },
+            Operation::InsertNode {
+                node: Node::Join {
+                    id: "c".to_string(),
+                    position: Position { x: 0.0, y: 0.0 },
+                    left_input: None,
+                    right_input: None,
+                    join_type: JoinType::Inner,
+                    columns: vec![JoinColumn { left: "id".to_string(), right: "key".to_string() }],
+                    outputs: HashSet::new()
+                }
+            },
+            Operation::SetInput {
+                id: "c".to_string(),
+                name: InputName::Primary,
+                input: Some("a".to_string())
+            },
+            Operation::SetInput {
+                id: "c".to_string(),
+                name: InputName::Secondary,
+                input: Some("b".to_string())
+            }
+        ];
+        doc.execute_operations(ops).unwrap();
+
+        // The right join keys are dropped and clashing right columns get a
+        // "_right" suffix, for every join type and in both the schema and
+        // the result.
+        for join_type in [JoinType::Inner, JoinType::Left, JoinType::Right, JoinType::Full] {
+            doc.execute_operations(vec![Operation::SetJoinType { id: "c".to_string(), join_type }]).unwrap();
+            let schema = doc.schemas(&context).remove("c").unwrap().unwrap();
+            let names: Vec<&str> = schema.columns().iter().map(|c| c.name.as_str()).collect();
+            assert_eq!(names, vec!["id", "name", "name_right", "total"]);
+            let df = doc.collect(&"c".to_string(), None, &context).unwrap();
+            assert_eq!(df.get_column_names(), names);
+        }
+
+        // A right join keeps the column layout of a left join.
+        let df = doc.collect(&"c".to_string(), None, &context).unwrap();
+        assert_eq!(df.height(), 3);
+        doc.execute_operations(vec![Operation::SetJoinType { id: "c".to_string(), join_type: JoinType::Right }]).unwrap();
+        let df = doc.collect(&"c".to_string(), None, &context).unwrap();
+        let mut ids: Vec<Option<i64>> = df.column("id").unwrap().i64().unwrap().into_iter().collect();
+        ids.sort();
+        assert_eq!(ids, vec![Some(1), Some(3)]);
+
+        // A cross join has no keys to drop.
+        doc.execute_operations(vec![Operation::SetJoinType { id: "c".to_string(), join_type: JoinType::Cross }]).unwrap();
+        let schema = doc.schemas(&context).remove("c").unwrap().unwrap();
+        let names: Vec<&str> = schema.columns().iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["id", "name", "key", "name_right", "total"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn materialize_sink() {
+        let (dir, context) = project("sink", &[("a.csv", "x,y\n1,a\n2,b\n")]);
+        let position = Position { x: 0.0, y: 0.0 };
+        let mut doc = Doc::new();
+        let ops = vec![
+            Operation::InsertNode { node: load_csv("a", "a.csv") },
+            Operation::InsertNode {
+                node: Node::WriteCsv {
+                    id: "b".to_string(),
+                    position: position.clone(),
+                    input: None,
+                    filename: "b.csv".to_string(),
+                    mode: WriteMode::ErrorIfExists,
+                    outputs: HashSet::new()
+                }
+            },
+            Operation::SetInput {
+                id: "b".to_string(),
+                name: InputName::Primary,
+                input: Some("a".to_string())
+            }
+        ];
+        doc.execute_operations(ops).unwrap();
+
+        let nodes = doc.extract_nodes(&"b".to_string()).unwrap();
+        let cache = Mutex::new(ResultCache::default());
+        let rows = materialize(&nodes, &"b".to_string(), &context, &cache).unwrap();
+        assert_eq!(rows, 2);
+        assert_eq!(std::fs::read_to_string(dir.join("b.csv")).unwrap(), "x,y\n1,a\n2,b\n");
+        assert!(materialize(&nodes, &"b".to_string(), &context, &cache).is_err());
+        assert!(materialize(&nodes, &"a".to_string(), &context, &cache).is_err());
+
+        for filename in ["b.parquet", "doc.polda", "doc.log", "b"] {
+            doc.execute_operations(vec![Operation::SetWriteCsvFilename {
+                id: "b".to_string(),
+                filename: filename.to_string()
+            }]).unwrap();
+            let nodes = doc.extract_nodes(&"b".to_string()).unwrap();
+            assert!(materialize(&nodes, &"b".to_string(), &context, &cache).is_err());
+            assert!(!dir.join(filename).exists());
+        }
+
+        // No temporary files are left behind.
+        let hidden = std::fs::read_dir(&dir)
+            .unwrap()
+            .filter(|entry| entry.as_ref().unwrap().file_name().to_string_lossy().starts_with('.'))
+            .count();
+        assert_eq!(hidden, 0);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
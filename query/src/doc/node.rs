@@ -5,13 +5,16 @@ use std::collections::HashSet;
 use crate::data_type::DataType;
 
 use super::Aggregate;
+use super::CsvOptions;
 use super::FilterPredicate;
 use super::JoinColumn;
 use super::JoinType;
+use super::ParquetCompression;
 use super::Position;
 use super::SelectColumn;
 use super::Sorter;
 use super::Value;
+use super::WriteMode;
 use super::types::case::Case;
 use super::types::compute::ComputeOperation;
 
@@ -83,6 +86,33 @@ pub enum Node {
         outputs: HashSet<String>
     },
     LoadCsv {
+        id: String,
+        position: Position,
+        filename: String,
+        #[serde(default)]
+        options: CsvOptions,
+        outputs: HashSet<String>
+    },
+    LoadDuckDb {
+        id: String,
+        position: Position,
+        filename: String,
+        table: String,
+        outputs: HashSet<String>
+    },
+    LoadIpc {
+        id: String,
+        position: Position,
+        filename: String,
+        outputs: HashSet<String>
+    },
+    LoadJson {
+        id: String,
+        position: Position,
+        filename: String,
+        outputs: HashSet<String>
+    },
+    LoadParquet {
         id: String,
         position: Position,
         filename: String,
@@ -108,6 +138,23 @@ pub enum Node {
         primary_input: Option<String>,
         secondary_input: Option<String>,
         outputs: HashSet<String>
+    },
+    WriteCsv {
+        id: String,
+        position: Position,
+        input: Option<String>,
+        filename: String,
+        mode: WriteMode,
+        outputs: HashSet<String>
+    },
+    WriteParquet {
+        id: String,
+        position: Position,
+        input: Option<String>,
+        filename: String,
+        mode: WriteMode,
+        compression: ParquetCompression,
+        outputs: HashSet<String>
     }
 }
 
@@ -187,6 +234,36 @@ impl Node {
             } => id,
 
             LoadCsv {
+                id,
+                position: _,
+                filename: _,
+                options: _,
+                outputs: _
+            } => id,
+
+            LoadDuckDb {
+                id,
+                position: _,
+                filename: _,
+                table: _,
+                outputs: _
+            } => id,
+
+            LoadIpc {
+                id,
+                position: _,
+                filename: _,
+                outputs: _
+            } => id,
+
+            LoadJson {
+                id,
+                position: _,
+                filename: _,
+                outputs: _
+            } => id,
+
+            LoadParquet {
                 id,
                 position: _,
                 filename: _,
@@ -215,6 +292,25 @@ impl Node {
                 secondary_input: _,
                 position: _,
                 outputs: _
+            } => id,
+
+            WriteCsv {
+                id,
+                position: _,
+                input: _,
+                filename: _,
+                mode: _,
+                outputs: _
+            } => id,
+
+            WriteParquet {
+                id,
+                position: _,
+                input: _,
+                filename: _,
+                mode: _,
+                compression: _,
+                outputs: _
             } => id
         }
     }
@@ -294,6 +390,36 @@ impl Node {
             } => vec![left_input, right_input],
 
             LoadCsv {
+                id: _,
+                position: _,
+                filename: _,
+                options: _,
+                outputs: _
+            } => vec![],
+
+            LoadDuckDb {
+                id: _,
+                position: _,
+                filename: _,
+                table: _,
+                outputs: _
+            } => vec![],
+
+            LoadIpc {
+                id: _,
+                position: _,
+                filename: _,
+                outputs: _
+            } => vec![],
+
+            LoadJson {
+                id: _,
+                position: _,
+                filename: _,
+                outputs: _
+            } => vec![],
+
+            LoadParquet {
                 id: _,
                 position: _,
                 filename: _,
@@ -322,7 +448,26 @@ impl Node {
                 secondary_input,
                 position: _,
                 outputs: _
-            } => vec![primary_input, secondary_input]
+            } => vec![primary_input, secondary_input],
+
+            WriteCsv {
+                id: _,
+                position: _,
+                input,
+                filename: _,
+                mode: _,
+                outputs: _
+            } => vec![input],
+
+            WriteParquet {
+                id: _,
+                position: _,
+                input,
+                filename: _,
+                mode: _,
+                compression: _,
+                outputs: _
+            } => vec![input]
         }
     }
 
@@ -414,6 +559,44 @@ impl Node {
             }
 
             LoadCsv {
+                id: _,
+                position: _,
+                filename: _,
+                options: _,
+                outputs
+            } => {
+                outputs.insert(id);
+            }
+
+            LoadDuckDb {
+                id: _,
+                position: _,
+                filename: _,
+                table: _,
+                outputs
+            } => {
+                outputs.insert(id);
+            }
+
+            LoadIpc {
+                id: _,
+                position: _,
+                filename: _,
+                outputs
+            } => {
+                outputs.insert(id);
+            }
+
+            LoadJson {
+                id: _,
+                position: _,
+                filename: _,
+                outputs
+            } => {
+                outputs.insert(id);
+            }
+
+            LoadParquet {
                 id: _,
                 position: _,
                 filename: _,
@@ -451,6 +634,29 @@ impl Node {
             } => {
                 outputs.insert(id);
             }
+
+            WriteCsv {
+                id: _,
+                position: _,
+                input: _,
+                filename: _,
+                mode: _,
+                outputs
+            } => {
+                outputs.insert(id);
+            }
+
+            WriteParquet {
+                id: _,
+                position: _,
+                input: _,
+                filename: _,
+                mode: _,
+                compression: _,
+                outputs
+            } => {
+                outputs.insert(id);
+            }
         }
     }
 
@@ -529,6 +735,36 @@ impl Node {
             } => outputs,
 
             LoadCsv {
+                id: _,
+                position: _,
+                filename: _,
+                options: _,
+                outputs
+            } => outputs,
+
+            LoadDuckDb {
+                id: _,
+                position: _,
+                filename: _,
+                table: _,
+                outputs
+            } => outputs,
+
+            LoadIpc {
+                id: _,
+                position: _,
+                filename: _,
+                outputs
+            } => outputs,
+
+            LoadJson {
+                id: _,
+                position: _,
+                filename: _,
+                outputs
+            } => outputs,
+
+            LoadParquet {
                 id: _,
                 position: _,
                 filename: _,
@@ -557,10 +793,75 @@ impl Node {
                 secondary_input: _,
                 position: _,
                 outputs
+            } => outputs,
+
+            WriteCsv {
+                id: _,
+                position: _,
+                input: _,
+                filename: _,
+                mode: _,
+                outputs
+            } => outputs,
+
+            WriteParquet {
+                id: _,
+                position: _,
+                input: _,
+                filename: _,
+                mode: _,
+                compression: _,
+                outputs
             } => outputs
         }
     }
 
+    /// The file a source node reads.
+    pub fn source_filename(&self) -> Option<&String> {
+        use Node::*;
+
+        match self {
+            LoadCsv {
+                id: _,
+                position: _,
+                filename,
+                options: _,
+                outputs: _
+            } => Some(filename),
+
+            LoadDuckDb {
+                id: _,
+                position: _,
+                filename,
+                table: _,
+                outputs: _
+            } => Some(filename),
+
+            LoadIpc {
+                id: _,
+                position: _,
+                filename,
+                outputs: _
+            } => Some(filename),
+
+            LoadJson {
+                id: _,
+                position: _,
+                filename,
+                outputs: _
+            } => Some(filename),
+
+            LoadParquet {
+                id: _,
+                position: _,
+                filename,
+                outputs: _
+            } => Some(filename),
+
+            _ => None
+        }
+    }
+
     pub fn remove_output(&mut self, id: &String) {
         use Node::*;
         match self {
@@ -649,6 +950,44 @@ impl Node {
             }
 
             LoadCsv {
+                id: _,
+                position: _,
+                filename: _,
+                options: _,
+                outputs
+            } => {
+                outputs.remove(id);
+            }
+
+            LoadDuckDb {
+                id: _,
+                position: _,
+                filename: _,
+                table: _,
+                outputs
+            } => {
+                outputs.remove(id);
+            }
+
+            LoadIpc {
+                id: _,
+                position: _,
+                filename: _,
+                outputs
+            } => {
+                outputs.remove(id);
+            }
+
+            LoadJson {
+                id: _,
+                position: _,
+                filename: _,
+                outputs
+            } => {
+                outputs.remove(id);
+            }
+
+            LoadParquet {
                 id: _,
                 position: _,
                 filename: _,
@@ -686,6 +1025,29 @@ impl Node {
             } => {
                 outputs.remove(id);
             }
+
+            WriteCsv {
+                id: _,
+                position: _,
+                input: _,
+                filename: _,
+                mode: _,
+                outputs
+            } => {
+                outputs.remove(id);
+            }
+
+            WriteParquet {
+                id: _,
+                position: _,
+                input: _,
+                filename: _,
+                mode: _,
+                compression: _,
+                outputs
+            } => {
+                outputs.remove(id);
+            }
         }
     }
 }
@@ -7,14 +7,17 @@ use super::Node;
 use super::InputName;
 use super::Position;
 use super::Aggregate;
+use super::CsvEncoding;
 use super::AggregateComputation;
 use super::FilterPredicate;
 use super::JoinColumn;
 use super::JoinType;
 use super::SelectColumn;
+use super::ParquetCompression;
 use super::SortDirection;
 use super::Sorter;
 use super::Value;
+use super::WriteMode;
 use super::types::case::Case;
 use super::types::compute::ComputeOperation;
 
@@ -188,6 +191,99 @@ pub enum Operation {
         filename: String
     },
 
+    SetLoadCsvDelimiter {
+        id: String,
+        delimiter: char
+    },
+
+    SetLoadCsvHasHeader {
+        id: String,
+        has_header: bool
+    },
+
+    SetLoadCsvQuoteChar {
+        id: String,
+        quote_char: Option<char>
+    },
+
+    SetLoadCsvNullValues {
+        id: String,
+        null_values: Vec<String>
+    },
+
+    SetLoadCsvSkipRows {
+        id: String,
+        skip_rows: usize
+    },
+
+    SetLoadCsvEncoding {
+        id: String,
+        encoding: CsvEncoding
+    },
+
+    SetLoadCsvInferSchemaLength {
+        id: String,
+        infer_schema_length: Option<usize>
+    },
+
+    SetLoadCsvColumnType {
+        id: String,
+        column: String,
+        data_type: Option<DataType>
+    },
+
+    // LoadDuckDb node operations:
+    SetLoadDuckDbFilename {
+        id: String,
+        filename: String
+    },
+    SetLoadDuckDbTable {
+        id: String,
+        table: String
+    },
+
+    // LoadIpc node operations:
+    SetLoadIpcFilename {
+        id: String,
+        filename: String
+    },
+
+    // LoadJson node operations:
+    SetLoadJsonFilename {
+        id: String,
+        filename: String
+    },
+
+    // LoadParquet node operations:
+    SetLoadParquetFilename {
+        id: String,
+        filename: String
+    },
+
+    // WriteCsv node operations:
+    SetWriteCsvFilename {
+        id: String,
+        filename: String
+    },
+    SetWriteCsvMode {
+        id: String,
+        mode: WriteMode
+    },
+
+    // WriteParquet node operations:
+    SetWriteParquetFilename {
+        id: String,
+        filename: String
+    },
+    SetWriteParquetMode {
+        id: String,
+        mode: WriteMode
+    },
+    SetWriteParquetCompression {
+        id: String,
+        compression: ParquetCompression
+    },
+
     // Join node operations:
     SetJoinType {
         id: String,
@@ -440,6 +536,109 @@ impl Operation {
                 filename: _
             } => id,
 
+            SetLoadCsvDelimiter {
+                id,
+                delimiter: _
+            } => id,
+
+            SetLoadCsvHasHeader {
+                id,
+                has_header: _
+            } => id,
+
+            SetLoadCsvQuoteChar {
+                id,
+                quote_char: _
+            } => id,
+
+            SetLoadCsvNullValues {
+                id,
+                null_values: _
+            } => id,
+
+            SetLoadCsvSkipRows {
+                id,
+                skip_rows: _
+            } => id,
+
+            SetLoadCsvEncoding {
+                id,
+                encoding: _
+            } => id,
+
+            SetLoadCsvInferSchemaLength {
+                id,
+                infer_schema_length: _
+            } => id,
+
+            SetLoadCsvColumnType {
+                id,
+                column: _,
+                data_type: _
+            } => id,
+
+            // LoadDuckDb node operations
+
+            SetLoadDuckDbFilename {
+                id,
+                filename: _
+            } => id,
+
+            SetLoadDuckDbTable {
+                id,
+                table: _
+            } => id,
+
+            // LoadIpc node operations
+
+            SetLoadIpcFilename {
+                id,
+                filename: _
+            } => id,
+
+            // LoadJson node operations
+
+            SetLoadJsonFilename {
+                id,
+                filename: _
+            } => id,
+
+            // LoadParquet node operations
+
+            SetLoadParquetFilename {
+                id,
+                filename: _
+            } => id,
+
+            // WriteCsv node operations
+
+            SetWriteCsvFilename {
+                id,
+                filename: _
+            } => id,
+
+            SetWriteCsvMode {
+                id,
+                mode: _
+            } => id,
+
+            // WriteParquet node operations
+
+            SetWriteParquetFilename {
+                id,
+                filename: _
+            } => id,
+
+            SetWriteParquetMode {
+                id,
+                mode: _
+            } => id,
+
+            SetWriteParquetCompression {
+                id,
+                compression: _
+            } => id,
+
             // Join node operations
 
             SetJoinType {
@@ -671,6 +870,96 @@ impl Operation {
                 SetLoadCsvFilename { id, filename }
             ) => SetLoadCsvFilename { id, filename },
 
+            (
+                InsertNode { node: _ },
+                SetLoadCsvDelimiter { id, delimiter }
+            ) => SetLoadCsvDelimiter { id, delimiter },
+
+            (
+                InsertNode { node: _ },
+                SetLoadCsvHasHeader { id, has_header }
+            ) => SetLoadCsvHasHeader { id, has_header },
+
+            (
+                InsertNode { node: _ },
+                SetLoadCsvQuoteChar { id, quote_char }
+            ) => SetLoadCsvQuoteChar { id, quote_char },
+
+            (
+                InsertNode { node: _ },
+                SetLoadCsvNullValues { id, null_values }
+            ) => SetLoadCsvNullValues { id, null_values },
+
+            (
+                InsertNode { node: _ },
+                SetLoadCsvSkipRows { id, skip_rows }
+            ) => SetLoadCsvSkipRows { id, skip_rows },
+
+            (
+                InsertNode { node: _ },
+                SetLoadCsvEncoding { id, encoding }
+            ) => SetLoadCsvEncoding { id, encoding },
+
+            (
+                InsertNode { node: _ },
+                SetLoadCsvInferSchemaLength { id, infer_schema_length }
+            ) => SetLoadCsvInferSchemaLength { id, infer_schema_length },
+
+            (
+                InsertNode { node: _ },
+                SetLoadCsvColumnType { id, column, data_type }
+            ) => SetLoadCsvColumnType { id, column, data_type },
+
+            (
+                InsertNode { node: _ },
+                SetLoadDuckDbFilename { id, filename }
+            ) => SetLoadDuckDbFilename { id, filename },
+
+            (
+                InsertNode { node: _ },
+                SetLoadDuckDbTable { id, table }
+            ) => SetLoadDuckDbTable { id, table },
+
+            (
+                InsertNode { node: _ },
+                SetLoadIpcFilename { id, filename }
+            ) => SetLoadIpcFilename { id, filename },
+
+            (
+                InsertNode { node: _ },
+                SetLoadJsonFilename { id, filename }
+            ) => SetLoadJsonFilename { id, filename },
+
+            (
+                InsertNode { node: _ },
+                SetLoadParquetFilename { id, filename }
+            ) => SetLoadParquetFilename { id, filename },
+
+            (
+                InsertNode { node: _ },
+                SetWriteCsvFilename { id, filename }
+            ) => SetWriteCsvFilename { id, filename },
+
+            (
+                InsertNode { node: _ },
+                SetWriteCsvMode { id, mode }
+            ) => SetWriteCsvMode { id, mode },
+
+            (
+                InsertNode { node: _ },
+                SetWriteParquetFilename { id, filename }
+            ) => SetWriteParquetFilename { id, filename },
+
+            (
+                InsertNode { node: _ },
+                SetWriteParquetMode { id, mode }
+            ) => SetWriteParquetMode { id, mode },
+
+            (
+                InsertNode { node: _ },
+                SetWriteParquetCompression { id, compression }
+            ) => SetWriteParquetCompression { id, compression },
+
             (
                 InsertNode { node: _ },
                 SetJoinType { id, join_type }
@@ -866,315 +1155,513 @@ impl Operation {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(DeleteNode { id })
+                    Some(DeleteNode { id })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                SetInput { id, name, input }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(SetInput { id, name, input })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                SetPosition { id, position }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(SetPosition { id, position })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                InsertAggregate { id, index, aggregate }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(InsertAggregate { id, index, aggregate })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                DeleteAggregate { id, index }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(DeleteAggregate { id, index })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                SetAggregateComputation { id, index, computation }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(SetAggregateComputation { id, index, computation })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                SetAggregateColumn { id, index, column }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(SetAggregateColumn { id, index, column })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                SetAggregateAlias { id, index, alias }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(SetAggregateAlias { id, index, alias })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                SetBinsName { id, name }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(SetBinsName { id, name })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                SetBinsColumn { id, column }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(SetBinsColumn { id, column })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                SetBinsLowerBound { id, lower_bound }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(SetBinsLowerBound { id, lower_bound })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                SetBinsUpperBound { id, upper_bound }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(SetBinsUpperBound { id, upper_bound })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                SetBinsCount { id, count }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(SetBinsCount { id, count })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                SetCaseName { id, name }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(SetCaseName { id, name })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                SetCaseDataType { id, data_type }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(SetCaseDataType { id, data_type })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                InsertCase { id, index, case }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(InsertCase { id, index, case })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                DeleteCase { id, index }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(DeleteCase { id, index })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                SetCaseColumn { id, index, column }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(SetCaseColumn { id, index, column })
+                }
+            }
+
+            (
+                InsertNode { node: pre_node },
+                SetCaseValue { id, index, value }
+            ) => {
+                if &id == pre_node.id() {
+                    None
+                } else {
+                    Some(SetCaseValue { id, index, value })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetInput { id, name, input }
+                SetCaseDefault { id, default }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetInput { id, name, input })
+                    Some(SetCaseDefault { id, default })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetPosition { id, position }
+                SetCastName { id, name }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetPosition { id, position })
+                    Some(SetCastName { id, name })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                InsertAggregate { id, index, aggregate }
+                SetCastColumn { id, column }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(InsertAggregate { id, index, aggregate })
+                    Some(SetCastColumn { id, column })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                DeleteAggregate { id, index }
+                SetCastDataType { id, data_type }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(DeleteAggregate { id, index })
+                    Some(SetCastDataType { id, data_type })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetAggregateComputation { id, index, computation }
+                SetComputeName { id, name }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetAggregateComputation { id, index, computation })
+                    Some(SetComputeName { id, name })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetAggregateColumn { id, index, column }
+                SetComputeColumn { id, column }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetAggregateColumn { id, index, column })
+                    Some(SetComputeColumn { id, column })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetAggregateAlias { id, index, alias }
+                SetComputeOperation { id, operation }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetAggregateAlias { id, index, alias })
+                    Some(SetComputeOperation { id, operation })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetBinsName { id, name }
+                SetFilterColumn { id, column }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetBinsName { id, name })
+                    Some(SetFilterColumn { id, column })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetBinsColumn { id, column }
+                SetFilterPredicate { id, predicate }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetBinsColumn { id, column })
+                    Some(SetFilterPredicate { id, predicate })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetBinsLowerBound { id, lower_bound }
+                SetLoadCsvFilename { id, filename }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetBinsLowerBound { id, lower_bound })
+                    Some(SetLoadCsvFilename { id, filename })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetBinsUpperBound { id, upper_bound }
+                SetLoadCsvDelimiter { id, delimiter }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetBinsUpperBound { id, upper_bound })
+                    Some(SetLoadCsvDelimiter { id, delimiter })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetBinsCount { id, count }
+                SetLoadCsvHasHeader { id, has_header }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetBinsCount { id, count })
+                    Some(SetLoadCsvHasHeader { id, has_header })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetCaseName { id, name }
+                SetLoadCsvQuoteChar { id, quote_char }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetCaseName { id, name })
+                    Some(SetLoadCsvQuoteChar { id, quote_char })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetCaseDataType { id, data_type }
+                SetLoadCsvNullValues { id, null_values }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetCaseDataType { id, data_type })
+                    Some(SetLoadCsvNullValues { id, null_values })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                InsertCase { id, index, case }
+                SetLoadCsvSkipRows { id, skip_rows }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(InsertCase { id, index, case })
+                    Some(SetLoadCsvSkipRows { id, skip_rows })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                DeleteCase { id, index }
+                SetLoadCsvEncoding { id, encoding }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(DeleteCase { id, index })
+                    Some(SetLoadCsvEncoding { id, encoding })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetCaseColumn { id, index, column }
+                SetLoadCsvInferSchemaLength { id, infer_schema_length }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetCaseColumn { id, index, column })
+                    Some(SetLoadCsvInferSchemaLength { id, infer_schema_length })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetCaseValue { id, index, value }
+                SetLoadCsvColumnType { id, column, data_type }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetCaseValue { id, index, value })
+                    Some(SetLoadCsvColumnType { id, column, data_type })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetCaseDefault { id, default }
+                SetLoadDuckDbFilename { id, filename }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetCaseDefault { id, default })
+                    Some(SetLoadDuckDbFilename { id, filename })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetCastName { id, name }
+                SetLoadDuckDbTable { id, table }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetCastName { id, name })
+                    Some(SetLoadDuckDbTable { id, table })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetCastColumn { id, column }
+                SetLoadIpcFilename { id, filename }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetCastColumn { id, column })
+                    Some(SetLoadIpcFilename { id, filename })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetCastDataType { id, data_type }
+                SetLoadJsonFilename { id, filename }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetCastDataType { id, data_type })
+                    Some(SetLoadJsonFilename { id, filename })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetComputeName { id, name }
+                SetLoadParquetFilename { id, filename }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetComputeName { id, name })
+                    Some(SetLoadParquetFilename { id, filename })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetComputeColumn { id, column }
+                SetWriteCsvFilename { id, filename }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetComputeColumn { id, column })
+                    Some(SetWriteCsvFilename { id, filename })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetComputeOperation { id, operation }
+                SetWriteCsvMode { id, mode }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetComputeOperation { id, operation })
+                    Some(SetWriteCsvMode { id, mode })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetFilterColumn { id, column }
+                SetWriteParquetFilename { id, filename }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetFilterColumn { id, column })
+                    Some(SetWriteParquetFilename { id, filename })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetFilterPredicate { id, predicate }
+                SetWriteParquetMode { id, mode }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetFilterPredicate { id, predicate })
+                    Some(SetWriteParquetMode { id, mode })
                 }
             }
 
             (
                 InsertNode { node: pre_node },
-                SetLoadCsvFilename { id, filename }
+                SetWriteParquetCompression { id, compression }
             ) => {
                 if &id == pre_node.id() {
                     None
                 } else {
-                    Some(SetLoadCsvFilename { id, filename })
+                    Some(SetWriteParquetCompression { id, compression })
                 }
             }
 
@@ -2200,6 +2687,204 @@ impl Operation {
                 }
             }
 
+            (
+                DeleteNode { id: pre_id },
+                SetLoadCsvDelimiter { id, delimiter }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetLoadCsvDelimiter { id, delimiter })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetLoadCsvHasHeader { id, has_header }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetLoadCsvHasHeader { id, has_header })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetLoadCsvQuoteChar { id, quote_char }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetLoadCsvQuoteChar { id, quote_char })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetLoadCsvNullValues { id, null_values }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetLoadCsvNullValues { id, null_values })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetLoadCsvSkipRows { id, skip_rows }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetLoadCsvSkipRows { id, skip_rows })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetLoadCsvEncoding { id, encoding }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetLoadCsvEncoding { id, encoding })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetLoadCsvInferSchemaLength { id, infer_schema_length }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetLoadCsvInferSchemaLength { id, infer_schema_length })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetLoadCsvColumnType { id, column, data_type }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetLoadCsvColumnType { id, column, data_type })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetLoadDuckDbFilename { id, filename }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetLoadDuckDbFilename { id, filename })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetLoadDuckDbTable { id, table }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetLoadDuckDbTable { id, table })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetLoadIpcFilename { id, filename }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetLoadIpcFilename { id, filename })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetLoadJsonFilename { id, filename }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetLoadJsonFilename { id, filename })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetLoadParquetFilename { id, filename }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetLoadParquetFilename { id, filename })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetWriteCsvFilename { id, filename }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetWriteCsvFilename { id, filename })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetWriteCsvMode { id, mode }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetWriteCsvMode { id, mode })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetWriteParquetFilename { id, filename }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetWriteParquetFilename { id, filename })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetWriteParquetMode { id, mode }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetWriteParquetMode { id, mode })
+                }
+            }
+
+            (
+                DeleteNode { id: pre_id },
+                SetWriteParquetCompression { id, compression }
+            ) => {
+                if &id == pre_id {
+                    None
+                } else {
+                    Some(SetWriteParquetCompression { id, compression })
+                }
+            }
+
             (
                 DeleteNode { id: pre_id },
                 SetJoinType { id, join_type }
@@ -2995,3 +3680,65 @@ pub fn validate_sequence(operations: &Vec<Operation>) -> Result<(), PoldaError>
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use super::*;
+
+    fn write_operations(id: &str) -> Vec<Operation> {
+        vec![
+            Operation::SetWriteCsvFilename { id: id.to_string(), filename: "a.csv".to_string() },
+            Operation::SetWriteCsvMode { id: id.to_string(), mode: WriteMode::Overwrite },
+            Operation::SetWriteParquetFilename { id: id.to_string(), filename: "a.parquet".to_string() },
+            Operation::SetWriteParquetMode { id: id.to_string(), mode: WriteMode::Overwrite },
+            Operation::SetWriteParquetCompression { id: id.to_string(), compression: ParquetCompression::Zstd }
+        ]
+    }
+
+    fn insert_node(id: &str) -> Operation {
+        Operation::InsertNode {
+            node: Node::WriteCsv {
+                id: id.to_string(),
+                position: Position { x: 0.0, y: 0.0 },
+                input: None,
+                filename: String::new(),
+                mode: WriteMode::ErrorIfExists,
+                outputs: HashSet::new()
+            }
+        }
+    }
+
+    fn same(a: &Operation, b: &Operation) -> bool {
+        serde_json::to_value(a).unwrap() == serde_json::to_value(b).unwrap()
+    }
+
+    #[test]
+    fn transform_write_operations() {
+        for op in write_operations("a") {
+            let deleted = Operation::DeleteNode { id: "a".to_string() };
+            assert!(op.clone().transform_forward(&deleted).is_none());
+            let other = Operation::DeleteNode { id: "b".to_string() };
+            assert!(same(&op.clone().transform_forward(&other).unwrap(), &op));
+
+            assert!(op.clone().transform_backward(&insert_node("a")).is_none());
+            assert!(same(&op.clone().transform_backward(&insert_node("b")).unwrap(), &op));
+            assert!(same(&op.clone().map(&insert_node("a")), &op));
+        }
+    }
+
+    #[test]
+    fn transform_write_batch() {
+        let deleted = vec![Operation::DeleteNode { id: "a".to_string() }];
+        assert!(transform_batch(write_operations("a"), &deleted).is_empty());
+        assert_eq!(transform_batch(write_operations("b"), &deleted).len(), 5);
+
+        let mut batch = vec![insert_node("a")];
+        batch.extend(write_operations("a"));
+        let transformed = transform_batch(batch.clone(), &deleted);
+        assert_eq!(transformed.len(), batch.len());
+        for (op, expected) in transformed.iter().zip(batch.iter()) {
+            assert!(same(op, expected));
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+
+use crate::data_type::DataType;
+
+/// How a CSV file is read.  The same options are used to infer the schema and
+/// to execute the query.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    pub has_header: bool,
+    pub quote_char: Option<char>,
+    /// Values that are read as null, e.g. `NA`.
+    pub null_values: Vec<String>,
+    pub skip_rows: usize,
+    pub encoding: CsvEncoding,
+    /// The number of rows used to infer the column types.  `None` reads the
+    /// whole file.
+    pub infer_schema_length: Option<usize>,
+    /// Column types that override the inferred types.
+    pub column_types: HashMap<String, DataType>
+}
+
+impl Default for CsvOptions {
+    fn default() -> CsvOptions {
+        CsvOptions {
+            delimiter: ',',
+            has_header: true,
+            quote_char: Some('"'),
+            null_values: vec![],
+            skip_rows: 0,
+            encoding: CsvEncoding::Utf8,
+            infer_schema_length: Some(100),
+            column_types: HashMap::new()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CsvEncoding {
+    Utf8,
+    /// Invalid UTF-8 sequences are replaced with `�`.
+    LossyUtf8
+}
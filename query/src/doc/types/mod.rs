@@ -3,10 +3,12 @@ use serde::Serialize;
 
 pub mod aggregate;
 pub mod case;
+pub mod csv;
 pub mod compute;
 pub mod filter;
 pub mod join;
 pub mod select;
+pub mod sink;
 pub mod sort;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
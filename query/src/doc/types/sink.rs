@@ -0,0 +1,37 @@
+use serde::Deserialize;
+use serde::Serialize;
+use std::path::Path;
+
+/// What a sink node does when its target file already exists.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WriteMode {
+    #[default]
+    ErrorIfExists,
+    Overwrite
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParquetCompression {
+    Uncompressed,
+    #[default]
+    Snappy,
+    Gzip,
+    Lz4,
+    Zstd
+}
+
+/// Check that a sink only writes files of its own format, so it can't
+/// overwrite documents, their logs or sources of another type.
+pub(crate) fn check_sink_filename(filename: &str, extension: &str) -> Result<(), String> {
+    let matches = Path::new(filename)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case(extension));
+    if matches {
+        Ok(())
+    } else {
+        Err(format!("Filename must end with .{}", extension))
+    }
+}
@@ -0,0 +1,581 @@
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::collections::HashSet;
+
+use crate::data_type::DataType;
+use crate::error::PoldaError;
+use crate::query::Schema;
+use super::Aggregate;
+use super::Case;
+use super::ComputeOperation;
+use super::FilterPredicate;
+use super::JoinColumn;
+use super::JoinType;
+use super::Node;
+use super::SelectColumn;
+use super::Sorter;
+use super::Value;
+use super::types::sink::check_sink_filename;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The node can't be queried.
+    Error,
+    /// The node depends on a broken node.
+    Warning
+}
+
+/// A problem with a node.  `field` and `index` point at the part of the node
+/// that needs fixing, e.g. field `columns` and index 2 is the third column of
+/// a select node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub node_id: String,
+    pub field: Option<String>,
+    pub index: Option<usize>,
+    pub severity: Severity,
+    pub msg: String
+}
+
+impl Diagnostic {
+    fn error(node_id: &str, field: &str, index: Option<usize>, msg: String) -> Diagnostic {
+        Diagnostic {
+            node_id: node_id.to_string(),
+            field: Some(field.to_string()),
+            index,
+            severity: Severity::Error,
+            msg
+        }
+    }
+}
+
+/// Validate every node of a document.  Unlike `collect`, this doesn't stop
+/// at the first error.  `schemas` are the inferred schemas of the nodes.
+pub fn validate(
+    nodes: &HashMap<String, Node>,
+    index: &[String],
+    schemas: &HashMap<String, Result<Schema, PoldaError>>
+) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let mut ids: Vec<&String> = nodes.keys().collect();
+    ids.sort();
+
+    for id in ids {
+        let node = nodes.get(id).unwrap();
+        let mut input_schemas = vec![];
+        let mut are_inputs_valid = true;
+
+        for (field, input) in input_fields(node).into_iter().zip(node.inputs()) {
+            let input = match input {
+                Some(input) => input,
+                None => {
+                    diagnostics.push(Diagnostic::error(id, field, None, format!("Node {} is missing an input", id)));
+                    are_inputs_valid = false;
+                    continue;
+                }
+            };
+            let input_node = match nodes.get(input) {
+                Some(input_node) => input_node,
+                None => {
+                    diagnostics.push(Diagnostic::error(id, field, None, format!("Node with id \"{}\" doesn't exist", input)));
+                    are_inputs_valid = false;
+                    continue;
+                }
+            };
+            if !input_node.outputs().contains(id) {
+                diagnostics.push(Diagnostic::error(id, field, None, format!("Node {} doesn't list {} as an output", input, id)));
+            }
+            match schemas.get(input) {
+                Some(Ok(schema)) => input_schemas.push(schema),
+                _ => {
+                    diagnostics.push(Diagnostic {
+                        node_id: id.clone(),
+                        field: Some(field.to_string()),
+                        index: None,
+                        severity: Severity::Warning,
+                        msg: format!("Input node {} is invalid", input)
+                    });
+                    are_inputs_valid = false;
+                }
+            }
+        }
+
+        let mut outputs: Vec<&String> = node.outputs().iter().collect();
+        outputs.sort();
+        for output in outputs {
+            match nodes.get(output) {
+                Some(output_node) => {
+                    if !output_node.inputs().contains(&&Some(id.clone())) {
+                        diagnostics.push(Diagnostic::error(id, "outputs", None, format!("Node {} doesn't take {} as an input", output, id)));
+                    }
+                }
+                None => {
+                    diagnostics.push(Diagnostic::error(id, "outputs", None, format!("Node with id \"{}\" doesn't exist", output)));
+                }
+            }
+        }
+
+        if is_in_cycle(nodes, id) {
+            diagnostics.push(Diagnostic {
+                node_id: id.clone(),
+                field: None,
+                index: None,
+                severity: Severity::Error,
+                msg: format!("Node {} is part of a cycle", id)
+            });
+            continue;
+        }
+
+        if !are_inputs_valid {
+            continue;
+        }
+
+        let node_diagnostics = column_diagnostics(node, &input_schemas);
+        if node_diagnostics.is_empty() {
+            // Fall back to the schema error for anything the checks above
+            // don't cover, e.g. unreadable files.
+            if let Some(Err(e)) = schemas.get(id) {
+                let field = if node.inputs().is_empty() {
+                    Some(String::from("filename"))
+                } else {
+                    None
+                };
+                diagnostics.push(Diagnostic {
+                    node_id: id.clone(),
+                    field,
+                    index: None,
+                    severity: Severity::Error,
+                    msg: e.to_string()
+                });
+            }
+        } else {
+            diagnostics.extend(node_diagnostics);
+        }
+    }
+
+    for (i, id) in index.iter().enumerate() {
+        if !nodes.contains_key(id) {
+            diagnostics.push(Diagnostic::error(id, "index", Some(i), format!("Node with id \"{}\" doesn't exist", id)));
+        }
+    }
+
+    diagnostics
+}
+
+/// The names of the input fields in the same order as `Node::inputs`.
+fn input_fields(node: &Node) -> Vec<&'static str> {
+    match node {
+        Node::Join {
+            id: _,
+            position: _,
+            left_input: _,
+            right_input: _,
+            join_type: _,
+            columns: _,
+            outputs: _
+        } => vec!["left_input", "right_input"],
+
+        Node::Union {
+            id: _,
+            position: _,
+            primary_input: _,
+            secondary_input: _,
+            outputs: _
+        } => vec!["primary_input", "secondary_input"],
+
+        _ => vec!["input"; node.inputs().len()]
+    }
+}
+
+fn is_in_cycle(nodes: &HashMap<String, Node>, id: &String) -> bool {
+    let mut visited = HashSet::new();
+    let mut ids = vec![id];
+    while let Some(next) = ids.pop() {
+        if let Some(node) = nodes.get(next) {
+            for input in node.inputs().into_iter().flatten() {
+                if input == id {
+                    return true;
+                }
+                if visited.insert(input) {
+                    ids.push(input);
+                }
+            }
+        }
+    }
+    false
+}
+
+fn value_column(value: &Value) -> Option<&String> {
+    if let Value::Column(column) = value {
+        Some(column)
+    } else {
+        None
+    }
+}
+
+/// Check a column of `schema` and an optional column operand.  Logical
+/// operations need booleans, others need operands of the same type.
+fn operand_diagnostics(
+    id: &str,
+    schema: &Schema,
+    column: &str,
+    operand: Option<&String>,
+    operand_field: &str,
+    is_logical: bool
+) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let dtype = match schema.get(column) {
+        Some(dtype) => dtype,
+        None => {
+            diagnostics.push(Diagnostic::error(id, "column", None, format!("Column \"{}\" doesn't exist", column)));
+            return diagnostics;
+        }
+    };
+    if is_logical && dtype != &DataType::Boolean {
+        diagnostics.push(Diagnostic::error(id, "column", None, format!("Column \"{}\" is not a boolean", column)));
+    }
+    if let Some(operand) = operand {
+        match schema.get(operand) {
+            None => {
+                diagnostics.push(Diagnostic::error(id, operand_field, None, format!("Column \"{}\" doesn't exist", operand)));
+            }
+            Some(other_dtype) if is_logical && other_dtype != &DataType::Boolean => {
+                diagnostics.push(Diagnostic::error(id, operand_field, None, format!("Column \"{}\" is not a boolean", operand)));
+            }
+            Some(other_dtype) if !is_logical && other_dtype != dtype => {
+                diagnostics.push(Diagnostic::error(id, operand_field, None, format!("Column \"{}\" and \"{}\" have different data types", column, operand)));
+            }
+            Some(_) => ()
+        }
+    }
+    diagnostics
+}
+
+/// Check the columns a node refers to against its input schemas.
+fn column_diagnostics(node: &Node, inputs: &[&Schema]) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    let id = node.id();
+    let missing = |column: &str| format!("Column \"{}\" doesn't exist", column);
+
+    match node {
+        Node::Aggregate {
+            id: _,
+            position: _,
+            input: _,
+            aggregates,
+            outputs: _
+        } => {
+            let mut new_columns = HashSet::new();
+            for (i, Aggregate { column, computation: _, alias }) in aggregates.iter().enumerate() {
+                if !inputs[0].contains(column) {
+                    diagnostics.push(Diagnostic::error(id, "aggregates", Some(i), missing(column)));
+                }
+                let new_column = if alias.is_empty() {
+                    column
+                } else {
+                    alias
+                };
+                if !new_columns.insert(new_column) {
+                    diagnostics.push(Diagnostic::error(id, "aggregates", Some(i), format!("Found duplicate columns \"{}\"", new_column)));
+                }
+            }
+        }
+
+        Node::Bins {
+            id: _,
+            position: _,
+            input: _,
+            name: _,
+            column,
+            lower_bound,
+            upper_bound,
+            count,
+            outputs: _
+        } => {
+            match inputs[0].get(column) {
+                None => diagnostics.push(Diagnostic::error(id, "column", None, missing(column))),
+                Some(dtype) if !dtype.is_numeric() => {
+                    diagnostics.push(Diagnostic::error(id, "column", None, format!("Column \"{}\" is not a number", column)));
+                }
+                Some(_) => ()
+            }
+            if *count == 0 || *count > u32::MAX as usize {
+                diagnostics.push(Diagnostic::error(id, "count", None, format!("Bins count must be between 1 and {}", u32::MAX)));
+            }
+            if lower_bound.is_nan() || upper_bound.is_nan() || lower_bound >= upper_bound {
+                diagnostics.push(Diagnostic::error(id, "lower_bound", None, format!("Bins lower bound must be less than the upper bound")));
+            }
+        }
+
+        Node::Case {
+            id: _,
+            position: _,
+            input: _,
+            name: _,
+            data_type,
+            cases,
+            default,
+            outputs: _
+        } => {
+            let schema = inputs[0];
+            for (i, Case { column, value }) in cases.iter().enumerate() {
+                match schema.get(column) {
+                    None => diagnostics.push(Diagnostic::error(id, "cases", Some(i), missing(column))),
+                    Some(dtype) if dtype != &DataType::Boolean => {
+                        diagnostics.push(Diagnostic::error(id, "cases", Some(i), format!("Case column \"{}\" is not a boolean", column)));
+                    }
+                    Some(_) => ()
+                }
+                if let Some(value) = value_column(value) {
+                    match schema.get(value) {
+                        None => diagnostics.push(Diagnostic::error(id, "cases", Some(i), missing(value))),
+                        Some(dtype) if dtype != data_type => {
+                            diagnostics.push(Diagnostic::error(id, "cases", Some(i), format!("Case value \"{}\" has incompatible data type", value)));
+                        }
+                        Some(_) => ()
+                    }
+                }
+            }
+            if let Some(value) = value_column(default) {
+                match schema.get(value) {
+                    None => diagnostics.push(Diagnostic::error(id, "default", None, missing(value))),
+                    Some(dtype) if dtype != data_type => {
+                        diagnostics.push(Diagnostic::error(id, "default", None, format!("Case value \"{}\" has incompatible data type", value)));
+                    }
+                    Some(_) => ()
+                }
+            }
+        }
+
+        Node::Cast {
+            id: _,
+            position: _,
+            input: _,
+            name: _,
+            column,
+            data_type: _,
+            outputs: _
+        } => {
+            if !inputs[0].contains(column) {
+                diagnostics.push(Diagnostic::error(id, "column", None, missing(column)));
+            }
+        }
+
+        Node::Compute {
+            id: _,
+            position: _,
+            input: _,
+            name: _,
+            column,
+            operation,
+            outputs: _
+        } => {
+            use ComputeOperation::*;
+            let (operand, is_logical) = match operation {
+                Add(v)
+                    | Subtract(v)
+                    | Multiply(v)
+                    | Divide(v)
+                    | IsEqualTo(v)
+                    | IsNotEqualTo(v)
+                    | IsLessThan(v)
+                    | IsLessThanEqual(v)
+                    | IsGreaterThan(v)
+                    | IsGreaterThanEqual(v) => (value_column(v), false),
+                And(v)
+                    | Or(v)
+                    | Xor(v) => (value_column(v), true),
+                IsNull
+                    | IsNotNull
+                    | Mean
+                    | Median
+                    | Min
+                    | Max => (None, false)
+            };
+            diagnostics.extend(operand_diagnostics(id, inputs[0], column, operand, "operation", is_logical));
+        }
+
+        Node::Filter {
+            id: _,
+            position: _,
+            input: _,
+            column,
+            predicate,
+            outputs: _
+        } => {
+            use FilterPredicate::*;
+            let (operand, is_logical) = match predicate {
+                IsEqualTo(v)
+                    | IsNotEqualTo(v)
+                    | IsLessThan(v)
+                    | IsLessThanEqual(v)
+                    | IsGreaterThan(v)
+                    | IsGreaterThanEqual(v) => (value_column(v), false),
+                And(v)
+                    | Or(v)
+                    | Xor(v) => (value_column(v), true),
+                IsNull
+                    | IsNotNull => (None, false)
+            };
+            diagnostics.extend(operand_diagnostics(id, inputs[0], column, operand, "predicate", is_logical));
+        }
+
+        Node::Join {
+            id: _,
+            position: _,
+            left_input: _,
+            right_input: _,
+            join_type,
+            columns,
+            outputs: _
+        } => {
+            if columns.is_empty() && !matches!(join_type, JoinType::Cross) {
+                diagnostics.push(Diagnostic::error(id, "columns", None, format!("JoinNode has no join columns")));
+            }
+            for (i, JoinColumn { left, right }) in columns.iter().enumerate() {
+                let left_dtype = inputs[0].get(left);
+                let right_dtype = inputs[1].get(right);
+                if left_dtype.is_none() {
+                    diagnostics.push(Diagnostic::error(id, "columns", Some(i), format!("Column \"{}\" doesn't exist in the left input table", left)));
+                }
+                if right_dtype.is_none() {
+                    diagnostics.push(Diagnostic::error(id, "columns", Some(i), format!("Column \"{}\" doesn't exist in the right input table", right)));
+                }
+                if let (Some(left_dtype), Some(right_dtype)) = (left_dtype, right_dtype) {
+                    if left_dtype != right_dtype {
+                        diagnostics.push(Diagnostic::error(id, "columns", Some(i), format!("Join columns \"{}\" and \"{}\" have different data types", left, right)));
+                    }
+                }
+            }
+        }
+
+        Node::LoadCsv {
+            id: _,
+            position: _,
+            filename: _,
+            options: _,
+            outputs: _
+        } => (),
+
+        Node::LoadDuckDb {
+            id: _,
+            position: _,
+            filename: _,
+            table: _,
+            outputs: _
+        } => (),
+
+        Node::LoadIpc {
+            id: _,
+            position: _,
+            filename: _,
+            outputs: _
+        } => (),
+
+        Node::LoadJson {
+            id: _,
+            position: _,
+            filename: _,
+            outputs: _
+        } => (),
+
+        Node::LoadParquet {
+            id: _,
+            position: _,
+            filename: _,
+            outputs: _
+        } => (),
+
+        Node::Select {
+            id: _,
+            position: _,
+            input: _,
+            columns,
+            outputs: _
+        } => {
+            let mut new_columns = HashSet::new();
+            for (i, SelectColumn { column, alias }) in columns.iter().enumerate() {
+                if !inputs[0].contains(column) {
+                    diagnostics.push(Diagnostic::error(id, "columns", Some(i), missing(column)));
+                }
+                let new_column = if alias.is_empty() {
+                    column
+                } else {
+                    alias
+                };
+                if !new_columns.insert(new_column) {
+                    diagnostics.push(Diagnostic::error(id, "columns", Some(i), format!("Found duplicate columns \"{}\" in SelectNode", new_column)));
+                }
+            }
+        }
+
+        Node::Sort {
+            id: _,
+            position: _,
+            input: _,
+            sorters,
+            outputs: _
+        } => {
+            for (i, Sorter { column, direction: _ }) in sorters.iter().enumerate() {
+                if !inputs[0].contains(column) {
+                    diagnostics.push(Diagnostic::error(id, "sorters", Some(i), missing(column)));
+                }
+            }
+        }
+
+        Node::Union {
+            id: _,
+            position: _,
+            primary_input: _,
+            secondary_input: _,
+            outputs: _
+        } => {
+            let primary = inputs[0];
+            let secondary = inputs[1];
+            for column in secondary.columns() {
+                match primary.get(&column.name) {
+                    None => {
+                        diagnostics.push(Diagnostic::error(id, "primary_input", None, format!("Column \"{}\" is missing in the first input table", column.name)));
+                    }
+                    Some(dtype) if dtype != &column.data_type => {
+                        diagnostics.push(Diagnostic::error(id, "secondary_input", None, format!("Column \"{}\" has different types", column.name)));
+                    }
+                    Some(_) => ()
+                }
+            }
+            for column in primary.columns() {
+                if !secondary.contains(&column.name) {
+                    diagnostics.push(Diagnostic::error(id, "secondary_input", None, format!("Column \"{}\" is missing in the second input table", column.name)));
+                }
+            }
+        }
+
+        Node::WriteCsv {
+            id: _,
+            position: _,
+            input: _,
+            filename,
+            mode: _,
+            outputs: _
+        } | Node::WriteParquet {
+            id: _,
+            position: _,
+            input: _,
+            filename,
+            mode: _,
+            compression: _,
+            outputs: _
+        } => {
+            let extension = if matches!(node, Node::WriteCsv { .. }) { "csv" } else { "parquet" };
+            if filename.is_empty() {
+                diagnostics.push(Diagnostic::error(id, "filename", None, format!("Filename can't be empty")));
+            } else if let Err(msg) = check_sink_filename(filename, extension) {
+                diagnostics.push(Diagnostic::error(id, "filename", None, msg));
+            }
+        }
+    }
+
+    diagnostics
+}
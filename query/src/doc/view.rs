@@ -0,0 +1,57 @@
+use polars::frame::DataFrame;
+use polars::prelude::IntoLazy;
+use polars::prelude::col;
+use serde::Deserialize;
+use serde::Serialize;
+
+use crate::error::PoldaError;
+use super::SortDirection;
+use super::Sorter;
+
+/// The part of a result a client is looking at.  Sorting a view doesn't
+/// change the doc.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct View {
+    #[serde(default)]
+    pub offset: usize,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub sorters: Vec<Sorter>
+}
+
+#[derive(Debug, Clone)]
+pub struct Page {
+    pub data: DataFrame,
+    pub offset: usize,
+    /// Number of rows of the whole result.
+    pub total_rows: usize
+}
+
+pub fn sort_frame(df: DataFrame, sorters: &[Sorter]) -> Result<DataFrame, PoldaError> {
+    let mut exprs = vec![];
+    let mut reverses = vec![];
+
+    for sorter in sorters.iter() {
+        let Sorter { column, direction } = sorter;
+        if df.column(column).is_err() {
+            return Err(PoldaError::QueryError(format!("Column \"{}\" doesn't exist", column)));
+        }
+        exprs.push(col(column));
+        reverses.push(matches!(direction, SortDirection::Desc));
+    }
+
+    Ok(df.lazy().sort_by_exprs(exprs, reverses, true).collect()?)
+}
+
+/// Slice a page out of a (sorted) result.
+pub fn page(df: &DataFrame, view: &View) -> Page {
+    let total_rows = df.height();
+    let offset = view.offset.min(total_rows);
+    let limit = view.limit.unwrap_or(total_rows);
+    Page {
+        data: df.slice(offset as i64, limit),
+        offset,
+        total_rows
+    }
+}
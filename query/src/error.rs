@@ -1,13 +1,19 @@
 use polars::prelude::PolarsError;
 use std::error::Error;
 use std::fmt;
+use std::io;
 
 #[derive(Debug)]
 pub enum PoldaError {
+    Canceled,
     DocError(String),
+    DuckDbError(duckdb::Error),
+    Forbidden(String),
+    IoError(io::Error),
     ParseError(String),
     PolarsError(PolarsError),
     QueryError(String),
+    SandboxError(String),
     OperationError(String),
     InternalError(String)
 }
@@ -16,11 +22,16 @@ impl fmt::Display for PoldaError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         use PoldaError::*;
         match self {
+            Canceled => write!(f, "Canceled"),
             DocError(msg) => write!(f, "DocError: {}", msg),
+            DuckDbError(e) => write!(f, "DuckDbError: {}", e),
+            Forbidden(msg) => write!(f, "Forbidden: {}", msg),
             InternalError(msg) => write!(f, "InternalError: {}", msg),
+            IoError(e) => write!(f, "IoError: {}", e),
             ParseError(msg) => write!(f, "ParseError: {}", msg),
             PolarsError(e) => write!(f, "PolarsError: {}", e),
             QueryError(msg) => write!(f, "QueryError: {}", msg),
+            SandboxError(msg) => write!(f, "SandboxError: {}", msg),
             OperationError(msg) => write!(f, "OperationError: {}", msg)
         }
     }
@@ -33,3 +44,15 @@ impl From<PolarsError> for PoldaError {
         PoldaError::PolarsError(error)
     }
 }
+
+impl From<duckdb::Error> for PoldaError {
+    fn from(error: duckdb::Error) -> PoldaError {
+        PoldaError::DuckDbError(error)
+    }
+}
+
+impl From<io::Error> for PoldaError {
+    fn from(error: io::Error) -> PoldaError {
+        PoldaError::IoError(error)
+    }
+}
@@ -0,0 +1,70 @@
+use polars::frame::DataFrame;
+use polars::prelude::CsvWriter;
+use polars::prelude::IpcWriter;
+use polars::prelude::JsonFormat;
+use polars::prelude::JsonWriter;
+use polars::prelude::ParquetCompression as CompressionOptions;
+use polars::prelude::ParquetWriter;
+use polars::prelude::SerWriter;
+use serde::Deserialize;
+use serde::Serialize;
+use std::io::Write;
+
+use crate::doc::ParquetCompression;
+use crate::error::PoldaError;
+
+/// File formats a result can be written to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExportFormat {
+    Csv,
+    Ipc,
+    Ndjson,
+    Parquet
+}
+
+impl ExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ipc => "arrow",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Parquet => "parquet"
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Ipc => "application/vnd.apache.arrow.file",
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Parquet => "application/vnd.apache.parquet"
+        }
+    }
+}
+
+pub fn write_frame<W: Write>(writer: W, df: &mut DataFrame, format: ExportFormat) -> Result<(), PoldaError> {
+    match format {
+        ExportFormat::Csv => CsvWriter::new(writer).finish(df)?,
+        ExportFormat::Ipc => IpcWriter::new(writer).finish(df)?,
+        ExportFormat::Ndjson => JsonWriter::new(writer).with_json_format(JsonFormat::JsonLines).finish(df)?,
+        ExportFormat::Parquet => ParquetWriter::new(writer).finish(df)?
+    }
+    Ok(())
+}
+
+pub fn write_parquet<W: Write>(
+    writer: W,
+    df: &mut DataFrame,
+    compression: ParquetCompression
+) -> Result<(), PoldaError> {
+    let compression = match compression {
+        ParquetCompression::Uncompressed => CompressionOptions::Uncompressed,
+        ParquetCompression::Snappy => CompressionOptions::Snappy,
+        ParquetCompression::Gzip => CompressionOptions::Gzip(None),
+        ParquetCompression::Lz4 => CompressionOptions::Lz4Raw,
+        ParquetCompression::Zstd => CompressionOptions::Zstd(None)
+    };
+    ParquetWriter::new(writer).with_compression(compression).finish(df)?;
+    Ok(())
+}
@@ -1,8 +1,12 @@
 pub use polars::frame::DataFrame;
 
+pub mod cache;
 pub mod column;
+pub mod context;
 pub mod data_type;
 pub mod doc;
 pub mod error;
+pub mod export;
 pub mod query;
+pub mod source;
 pub mod utils;
@@ -1,36 +1,155 @@
+use duckdb::AccessMode;
+use duckdb::Config;
+use duckdb::Connection;
+use duckdb::arrow::array::ArrayRef as DuckDbArrayRef;
+use duckdb::arrow::ffi::FFI_ArrowArray;
+use duckdb::arrow::ffi::FFI_ArrowSchema;
+use duckdb::arrow::ffi::export_array_into_raw;
+use duckdb::params;
+use polars::export::arrow::ffi;
 use polars::frame::DataFrame;
+use polars::prelude::ArrayRef;
+use polars::prelude::Series;
+use std::path::Path;
 use std::sync::Arc;
 
+use crate::column::Column;
+use crate::context::CancellationToken;
+use crate::context::ExecutionContext;
+use crate::data_type::DataType;
 use crate::doc::Node;
 use crate::error::PoldaError;
 use super::Schema;
 use super::SqlQuery;
+use super::sql::Dialect;
+use super::sql::SqlInput;
+use super::sql::data_type_from_sql;
+use super::sql::data_type_to_sql;
+use super::sql::node_to_sql;
+use super::sql::quote_ident;
 
 #[derive(Debug, Clone)]
 pub struct DuckDbQuery {
     query: Vec<Arc<SqlQuery>>,
     path: Arc<String>,
-    schema: Arc<Schema>
+    schema: Arc<Schema>,
+    token: CancellationToken
 }
 
 impl DuckDbQuery {
     pub fn collect(self) -> Result<DataFrame, PoldaError> {
-        todo!()
+        let target = self.query
+            .last()
+            .ok_or(PoldaError::QueryError(format!("DuckDbQuery is empty")))?;
+        let columns = self.schema.columns();
+        let ctes: Vec<String> = self.query
+            .iter()
+            .map(|q| format!("{} AS ({})", quote_ident(&q.id), q.query))
+            .collect();
+        let select = columns
+            .iter()
+            .map(|column| {
+                let name = quote_ident(&column.name);
+                Ok(format!("{} AS {}", read_expr(&name, &column.data_type, 0)?, name))
+            })
+            .collect::<Result<Vec<String>, PoldaError>>()?;
+        let sql = format!(
+            "WITH {} SELECT {} FROM {}",
+            ctes.join(", "),
+            select.join(", "),
+            quote_ident(&target.id)
+        );
+
+        let conn = open(self.path.as_str())?;
+        let mut stmt = conn.prepare(&sql)?;
+        let mut chunks: Vec<Vec<ArrayRef>> = vec![vec![]; columns.len()];
+        for batch in stmt.query_arrow(params![])? {
+            if self.token.is_canceled() {
+                return Err(PoldaError::Canceled);
+            }
+            for (i, column_chunks) in chunks.iter_mut().enumerate() {
+                column_chunks.push(import_array(batch.column(i).clone())?);
+            }
+        }
+
+        let mut series = Vec::with_capacity(columns.len());
+        for (column, column_chunks) in columns.iter().zip(chunks) {
+            let dtype = column.data_type.into_polars();
+            let column_series = if column_chunks.is_empty() {
+                Series::new_empty(&column.name, &dtype)
+            } else {
+                Series::try_from((column.name.as_str(), column_chunks))?.cast(&dtype)?
+            };
+            series.push(column_series);
+        }
+
+        Ok(DataFrame::new(series)?)
     }
 
     pub fn from_node(
-        _node: &Node,
-        mut _inputs: Vec<DuckDbQuery>
+        node: &Node,
+        inputs: Vec<DuckDbQuery>,
+        context: &ExecutionContext
     ) -> Result<DuckDbQuery, PoldaError> {
-        todo!()
+        let input_schemas = inputs
+            .iter()
+            .map(|input| input.schema.as_ref().clone())
+            .collect();
+        let schema = Schema::try_from_node(node, input_schemas, context)?;
+
+        let path = if let Node::LoadDuckDb {
+            id: _,
+            position: _,
+            filename,
+            table: _,
+            outputs: _
+        } = node {
+            let path = context.resolve(filename)?;
+            Arc::new(path.to_string_lossy().to_string())
+        } else {
+            let first = inputs
+                .first()
+                .ok_or(PoldaError::QueryError(format!("Node \"{}\" is missing input node", node.id())))?;
+            for input in inputs.iter() {
+                if !first.same_backend(input) {
+                    return Err(PoldaError::QueryError(format!("Can't combine tables from different DuckDB databases")));
+                }
+            }
+            first.path()
+        };
+
+        // Merge the CTEs of the inputs.  An input may be shared by several
+        // branches, so keep only the first occurrence.
+        let mut query: Vec<Arc<SqlQuery>> = vec![];
+        for input in inputs.iter() {
+            for q in input.query.iter() {
+                if !query.iter().any(|existing| existing.id == q.id) {
+                    query.push(q.clone());
+                }
+            }
+        }
+
+        let mut sql_inputs = Vec::with_capacity(inputs.len());
+        for input in inputs.iter() {
+            let name = input.query
+                .last()
+                .ok_or(PoldaError::QueryError(format!("DuckDbQuery is empty")))?;
+            sql_inputs.push(SqlInput { name: &name.id, schema: &input.schema });
+        }
+
+        let sql = node_to_sql(node, &sql_inputs, &schema, Dialect::DuckDb)?;
+        query.push(Arc::new(SqlQuery { id: node.id().clone(), query: sql }));
+
+        Ok(DuckDbQuery::new(query, path, Arc::new(schema), context.token().clone()))
     }
 
     pub fn new(
         query: Vec<Arc<SqlQuery>>,
         path: Arc<String>,
-        schema: Arc<Schema>
+        schema: Arc<Schema>,
+        token: CancellationToken
     ) -> DuckDbQuery {
-        DuckDbQuery { query, path, schema }
+        DuckDbQuery { query, path, schema, token }
     }
 
     pub fn path(&self) -> Arc<String> {
@@ -49,3 +168,90 @@ impl DuckDbQuery {
         self.schema.clone()
     }
 }
+
+/// Read the schema of a table in a DuckDB database.
+pub fn table_schema(path: &Path, table: &str) -> Result<Schema, PoldaError> {
+    let conn = open(path)?;
+    let mut stmt = conn.prepare(
+        "SELECT column_name, data_type FROM information_schema.columns WHERE table_name = ? ORDER BY ordinal_position"
+    )?;
+    let mut rows = stmt.query(params![table])?;
+    let mut columns = vec![];
+    while let Some(row) = rows.next()? {
+        let name: String = row.get(0)?;
+        let dtype: String = row.get(1)?;
+        columns.push(Column { name, data_type: data_type_from_sql(&dtype)? });
+    }
+    if columns.is_empty() {
+        return Err(PoldaError::QueryError(format!("Table \"{}\" doesn't exist in \"{}\"", table, path.display())));
+    }
+    Ok(Schema::new(columns))
+}
+
+/// Open the database read-only so that several queries can read it at the
+/// same time.
+fn open<P: AsRef<Path>>(path: P) -> Result<Connection, PoldaError> {
+    let config = Config::default().access_mode(AccessMode::ReadOnly)?;
+    Ok(Connection::open_with_flags(path, config)?)
+}
+
+/// The expression that reads a column as its schema type.  Polars can't read
+/// intervals, so they're read as microseconds.
+fn read_expr(expr: &str, dtype: &DataType, depth: usize) -> Result<String, PoldaError> {
+    match dtype {
+        DataType::Duration => Ok(format!("CAST(epoch({}) * 1000000 AS BIGINT)", expr)),
+        DataType::List(inner) if has_duration(inner) => {
+            let var = format!("x{}", depth);
+            Ok(format!("list_transform({}, {} -> {})", expr, var, read_expr(&var, inner, depth + 1)?))
+        }
+        _ => Ok(format!("CAST({} AS {})", expr, data_type_to_sql(dtype, Dialect::DuckDb)?))
+    }
+}
+
+fn has_duration(dtype: &DataType) -> bool {
+    match dtype {
+        DataType::Duration => true,
+        DataType::List(inner) => has_duration(inner),
+        _ => false
+    }
+}
+
+/// Move an array from DuckDB's arrow to the arrow of Polars through the C
+/// data interface.
+fn import_array(array: DuckDbArrayRef) -> Result<ArrayRef, PoldaError> {
+    let mut ffi_array = Box::new(ffi::ArrowArray::empty());
+    let mut ffi_schema = Box::new(ffi::ArrowSchema::empty());
+    // Both structs are laid out as defined by the C data interface.
+    unsafe {
+        export_array_into_raw(
+            array,
+            &mut *ffi_array as *mut ffi::ArrowArray as *mut FFI_ArrowArray,
+            &mut *ffi_schema as *mut ffi::ArrowSchema as *mut FFI_ArrowSchema
+        ).map_err(|e| PoldaError::QueryError(format!("Failed to export a DuckDB array: {}", e)))?;
+        let field = ffi::import_field_from_c(&ffi_schema)
+            .map_err(|e| PoldaError::QueryError(format!("Failed to import a DuckDB array: {}", e)))?;
+        let array = ffi::import_array_from_c(*ffi_array, field.data_type)
+            .map_err(|e| PoldaError::QueryError(format!("Failed to import a DuckDB array: {}", e)))?;
+        Ok(array)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_expressions() {
+        let dtype = DataType::DateTime;
+        assert_eq!(read_expr("\"a\"", &dtype, 0).unwrap(), "CAST(\"a\" AS TIMESTAMP)");
+        let dtype = DataType::Duration;
+        assert_eq!(read_expr("\"a\"", &dtype, 0).unwrap(), "CAST(epoch(\"a\") * 1000000 AS BIGINT)");
+        let dtype = DataType::List(Box::new(DataType::Time));
+        assert_eq!(read_expr("\"a\"", &dtype, 0).unwrap(), "CAST(\"a\" AS TIME[])");
+        let dtype = DataType::List(Box::new(DataType::List(Box::new(DataType::Duration))));
+        assert_eq!(
+            read_expr("\"a\"", &dtype, 0).unwrap(),
+            "list_transform(\"a\", x0 -> list_transform(x0, x1 -> CAST(epoch(x1) * 1000000 AS BIGINT)))"
+        );
+    }
+}
@@ -1,19 +1,22 @@
 use polars::frame::DataFrame;
-use polars::prelude::Field;
-use polars::prelude::IntoLazy;
-use std::collections::HashMap;
-use std::sync::Arc;
 
-use crate::data_type::DataType;
+use crate::context::ExecutionContext;
 
 mod duck_db_query;
 mod polars_query;
 mod schema;
+mod sql;
 mod types;
 
 pub use duck_db_query::DuckDbQuery;
 pub use polars_query::PolarsQuery;
+pub use polars_query::scan_csv;
+pub use polars_query::scan_ipc;
+pub use polars_query::scan_json;
+pub use polars_query::scan_parquet;
 pub use schema::Schema;
+pub use sql::Dialect;
+pub use sql::compile_sql;
 pub use types::SqlQuery;
 
 use crate::error::PoldaError;
@@ -43,14 +46,26 @@ impl Query {
         }
     }
 
-    pub fn from_node(node: &Node, inputs: Vec<Query>) -> Result<Query, PoldaError> {
+    pub fn from_node(
+        node: &Node,
+        inputs: Vec<Query>,
+        context: &ExecutionContext
+    ) -> Result<Query, PoldaError> {
         use Node::*;
         match node {
-            LoadCsv { id: _, position: _, filename: _, outputs: _ } => {
-                PolarsQuery::from_node(node, vec![])
+            LoadCsv { id: _, position: _, filename: _, options: _, outputs: _ }
+                | LoadIpc { id: _, position: _, filename: _, outputs: _ }
+                | LoadJson { id: _, position: _, filename: _, outputs: _ }
+                | LoadParquet { id: _, position: _, filename: _, outputs: _ } => {
+                PolarsQuery::from_node(node, vec![], context)
                     .map(|q| Query::Polars(q))
             }
 
+            LoadDuckDb { id: _, position: _, filename: _, table: _, outputs: _ } => {
+                DuckDbQuery::from_node(node, vec![], context)
+                    .map(|q| Query::DuckDb(q))
+            }
+
             // Nodes that require input table(s).
             _ => {
                 if let Some(first) = inputs.first() {
@@ -60,7 +75,7 @@ impl Query {
                             for input in inputs {
                                 duck_inputs.push(input.duck_db()?);
                             }
-                            DuckDbQuery::from_node(node, duck_inputs)
+                            DuckDbQuery::from_node(node, duck_inputs, context)
                                 .map(|q| Query::DuckDb(q))
                         }
 
@@ -69,7 +84,7 @@ impl Query {
                             for input in inputs {
                                 polars_inputs.push(input.polars()?);
                             }
-                            PolarsQuery::from_node(node, polars_inputs)
+                            PolarsQuery::from_node(node, polars_inputs, context)
                                 .map(|q| Query::Polars(q))
                         }
                     }
@@ -87,20 +102,7 @@ impl Query {
         use Query::*;
         match self {
             Polars(q) => Ok(q),
-            other => {
-                let frame = other.collect()?;
-                let mut schema = HashMap::new();
-                for field in frame.schema().iter_fields() {
-                    let Field { name, dtype } = field;
-                    let dtype = DataType::try_from(dtype)?;
-                    schema.insert(name, dtype);
-                }
-                let query = PolarsQuery {
-                    frame: IntoLazy::lazy(frame),
-                    schema: Schema(Arc::new(schema))
-                };
-                Ok(query)
-            }
+            other => PolarsQuery::try_from_frame(other.collect()?)
         }
     }
 
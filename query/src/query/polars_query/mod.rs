@@ -1,3 +1,4 @@
+use polars::datatypes::DataType as PolarsDataType;
 use polars::frame::DataFrame;
 use polars::frame::hash_join::JoinType as PolarsJoinType;
 use polars::lazy::prelude::lit;
@@ -5,9 +6,16 @@ use polars::lazy::frame::LazyFrame;
 use polars::prelude::Null;
 use polars::prelude::col;
 use polars::prelude::concat;
+use polars::prelude::IntoLazy;
 use polars::prelude::Expr;
+use polars::prelude::CsvEncoding as PolarsCsvEncoding;
 use polars::prelude::LazyCsvReader;
+use polars::prelude::LazyJsonLineReader;
 use polars::prelude::Literal;
+use polars::prelude::NullValues;
+use polars::prelude::Schema as PolarsSchema;
+use polars::prelude::ScanArgsIpc;
+use polars::prelude::ScanArgsParquet;
 use polars::prelude::when;
 use polars::prelude::WhenThen;
 use polars::prelude::WhenThenThen;
@@ -15,10 +23,14 @@ use std::ops::Add;
 use std::ops::Div;
 use std::ops::Mul;
 use std::ops::Sub;
+use std::path::Path;
 
+use crate::context::ExecutionContext;
 use crate::data_type::DataType;
 use crate::doc::Case;
 use crate::doc::ComputeOperation;
+use crate::doc::CsvEncoding;
+use crate::doc::CsvOptions;
 use crate::doc::JoinColumn;
 use crate::doc::JoinType;
 use crate::doc::SelectColumn;
@@ -45,13 +57,14 @@ impl PolarsQuery {
 
     pub fn from_node(
         node: &Node,
-        inputs: Vec<PolarsQuery>
+        inputs: Vec<PolarsQuery>,
+        context: &ExecutionContext
     ) -> Result<PolarsQuery, PoldaError> {
         let input_schemas = inputs
             .iter()
             .map(|input| input.schema.clone())
             .collect();
-        let schema = Schema::try_from_node(node, input_schemas)?;
+        let schema = Schema::try_from_node(node, input_schemas, context)?;
 
         let frame: LazyFrame = match node {
             Node::Aggregate {
@@ -103,15 +116,38 @@ impl PolarsQuery {
                 id: _,
                 position: _,
                 input: _,
-                name: _,
-                column: _,
-                lower_bound: _,
-                upper_bound: _,
-                count: _,
+                name,
+                column,
+                lower_bound,
+                upper_bound,
+                count,
                 outputs: _
             } => {
-                // TODO: We haven't do anything here.
-                unimplemented!("Bins query is not implemented")
+                // Bucket `i` covers `[lower + i * width, lower + (i + 1) * width)`
+                // except the last bucket which also includes the upper bound.
+                // Values outside the range are null.  The bounds and count are
+                // validated by the Schema builder.
+                let width = (upper_bound - lower_bound) / *count as f64;
+                let value = col(&**column).cast(PolarsDataType::Float64);
+                let index = ((value.clone() - lit(*lower_bound)) / lit(width))
+                    .cast(PolarsDataType::UInt32);
+                let last = lit(*count as u32 - 1);
+                let index = when(index.clone().gt(last.clone()))
+                    .then(last)
+                    .otherwise(index);
+                let out_of_range = value.clone().lt(lit(*lower_bound))
+                    .or(value.gt(lit(*upper_bound)));
+                let expr = when(out_of_range)
+                    .then(Null{}.lit().cast(PolarsDataType::UInt32))
+                    .otherwise(index)
+                    .alias(&**name);
+
+                inputs
+                    .into_iter()
+                    .next()
+                    .unwrap()
+                    .frame
+                    .with_column(expr)
             }
 
             Node::Case {
@@ -164,7 +200,7 @@ impl PolarsQuery {
                     WhenThenMaybeThen::WhenThenThen(wt) => {
                         frame.with_column(wt.otherwise(default).alias(&*name))
                     }
-                    _ => frame
+                    _ => frame.with_column(default.alias(&*name))
                 };
 
                 frame
@@ -207,7 +243,7 @@ impl PolarsQuery {
 
                 let mut expr = col(&**column);
                 // Column is guaranteed to exists by the Schema builder.
-                let dtype = schema.0.get(column).unwrap();
+                let dtype = schema.get(column).unwrap();
 
                 use ComputeOperation::*;
                 expr = match operation {
@@ -264,7 +300,7 @@ impl PolarsQuery {
                 outputs: _
             } => {
                 let expr = col(column);
-                let dtype = schema.0
+                let dtype = schema
                     .get(column)
                     .unwrap();
 
@@ -313,11 +349,6 @@ impl PolarsQuery {
                 let mut inputs = inputs.into_iter();
                 let left = inputs.next().unwrap();
                 let right = inputs.next().unwrap();
-                let (left, right) = if let JoinType::Right = join_type {
-                    (right, left)
-                } else {
-                    (left, right)
-                };
                 let mut left_exprs = vec![];
                 let mut right_exprs = vec![];
 
@@ -346,15 +377,7 @@ impl PolarsQuery {
                         )
                     }
 
-                    JoinType::Right => {
-                        // Switch the frames and the expresions.
-                        right.frame.join(
-                            left.frame,
-                            right_exprs,
-                            left_exprs,
-                            PolarsJoinType::Left
-                        )
-                    }
+                    JoinType::Right => right_join(left, right, columns),
 
                     JoinType::Full => {
                         left.frame.join(
@@ -370,14 +393,42 @@ impl PolarsQuery {
             }
 
             Node::LoadCsv {
+                id: _,
+                position: _,
+                filename,
+                options,
+                outputs: _
+            } => scan_csv(&context.resolve(filename)?, options)?,
+
+            Node::LoadIpc {
                 id: _,
                 position: _,
                 filename,
                 outputs: _
+            } => scan_ipc(&context.resolve(filename)?)?,
+
+            Node::LoadJson {
+                id: _,
+                position: _,
+                filename,
+                outputs: _
+            } => scan_json(&context.resolve(filename)?)?,
+
+            Node::LoadParquet {
+                id: _,
+                position: _,
+                filename,
+                outputs: _
+            } => scan_parquet(&context.resolve(filename)?)?,
+
+            Node::LoadDuckDb {
+                id: _,
+                position: _,
+                filename: _,
+                table: _,
+                outputs: _
             } => {
-                // TODO: Use context that specify project dir and force path
-                // to be directly under the project dir.
-                LazyCsvReader::new(filename).finish()?
+                return Err(PoldaError::QueryError(format!("LoadDuckDbNode can't be queried with Polars")));
             }
 
             Node::Select {
@@ -446,7 +497,36 @@ impl PolarsQuery {
                 let mut inputs = inputs.into_iter();
                 let first = inputs.next().unwrap();
                 let second = inputs.next().unwrap();
-                concat([first.frame, second.frame], false, true)?
+                // Align the columns of the second table with the first one.
+                let columns: Vec<Expr> = first.schema
+                    .columns()
+                    .iter()
+                    .map(|column| col(&column.name))
+                    .collect();
+                concat([first.frame, second.frame.select(columns)], false, true)?
+            }
+
+            Node::WriteCsv {
+                id: _,
+                position: _,
+                input: _,
+                filename: _,
+                mode: _,
+                outputs: _
+            } | Node::WriteParquet {
+                id: _,
+                position: _,
+                input: _,
+                filename: _,
+                mode: _,
+                compression: _,
+                outputs: _
+            } => {
+                inputs
+                    .into_iter()
+                    .next()
+                    .unwrap()
+                    .frame
             }
         };
 
@@ -456,6 +536,127 @@ impl PolarsQuery {
     pub fn new(frame: LazyFrame, schema: Schema) -> PolarsQuery {
         PolarsQuery { frame, schema }
     }
+
+    /// Wrap a materialized frame, e.g. the result of another backend.
+    pub fn try_from_frame(frame: DataFrame) -> Result<PolarsQuery, PoldaError> {
+        let frame = frame.lazy();
+        let schema = Schema::try_from_frame(&frame)?;
+        Ok(PolarsQuery { frame, schema })
+    }
+}
+
+/// A right join as a swapped left join, in the column layout of the schema:
+/// the left columns followed by the right columns that aren't join keys.
+/// The columns get unique temporary names first, so restoring the names
+/// can't mix up columns that have the same name on both sides.
+fn right_join(left: PolarsQuery, right: PolarsQuery, columns: &[JoinColumn]) -> LazyFrame {
+    let left_names: Vec<&String> = left.schema.columns().iter().map(|c| &c.name).collect();
+    let right_names: Vec<&String> = right.schema.columns().iter().map(|c| &c.name).collect();
+    let left_tmp: Vec<String> = (0..left_names.len()).map(|i| format!("__left_{}", i)).collect();
+    let right_tmp: Vec<String> = (0..right_names.len()).map(|i| format!("__right_{}", i)).collect();
+    // Join columns are guaranteed to exist by the Schema builder.
+    let left_tmp_of = |name: &String| &left_tmp[left_names.iter().position(|n| *n == name).unwrap()];
+    let right_tmp_of = |name: &String| &right_tmp[right_names.iter().position(|n| *n == name).unwrap()];
+
+    let mut left_exprs = vec![];
+    let mut right_exprs = vec![];
+    for JoinColumn { left, right } in columns.iter() {
+        left_exprs.push(col(left_tmp_of(left)));
+        right_exprs.push(col(right_tmp_of(right)));
+    }
+
+    let mut exprs = vec![];
+    for (name, tmp) in left_names.iter().zip(left_tmp.iter()) {
+        // The left keys are dropped by the swapped join.
+        let tmp = match columns.iter().find(|c| &c.left == *name) {
+            Some(JoinColumn { left: _, right }) => right_tmp_of(right),
+            None => tmp
+        };
+        exprs.push(col(tmp).alias(name));
+    }
+    for (name, tmp) in right_names.iter().zip(right_tmp.iter()) {
+        if columns.iter().any(|c| &c.right == *name) {
+            continue;
+        }
+        if left.schema.contains(name) {
+            exprs.push(col(tmp).alias(&format!("{}_right", name)));
+        } else {
+            exprs.push(col(tmp).alias(name));
+        }
+    }
+
+    right.frame
+        .rename(&right_names, &right_tmp)
+        .join(
+            left.frame.rename(&left_names, &left_tmp),
+            right_exprs,
+            left_exprs,
+            PolarsJoinType::Left
+        )
+        .select(exprs)
+}
+
+/// Lazily scan a CSV file.  The schema is inferred here, so the Schema
+/// builder and the query always agree.
+pub fn scan_csv(path: &Path, options: &CsvOptions) -> Result<LazyFrame, PoldaError> {
+    let delimiter = ascii_byte(options.delimiter, "delimiter")?;
+    let quote_char = match options.quote_char {
+        Some(c) => Some(ascii_byte(c, "quote char")?),
+        None => None
+    };
+    let null_values = if options.null_values.is_empty() {
+        None
+    } else {
+        Some(NullValues::AllColumns(options.null_values.clone()))
+    };
+    let encoding = match options.encoding {
+        CsvEncoding::Utf8 => PolarsCsvEncoding::Utf8,
+        CsvEncoding::LossyUtf8 => PolarsCsvEncoding::LossyUtf8
+    };
+    let mut dtypes = PolarsSchema::new();
+    for (column, dtype) in options.column_types.iter() {
+        dtypes.with_column(column.clone(), dtype.into_polars());
+    }
+    let dtypes = if dtypes.is_empty() {
+        None
+    } else {
+        Some(&dtypes)
+    };
+
+    let frame = LazyCsvReader::new(path)
+        .with_delimiter(delimiter)
+        .has_header(options.has_header)
+        .with_quote_char(quote_char)
+        .with_null_values(null_values)
+        .with_skip_rows(options.skip_rows)
+        .with_encoding(encoding)
+        .with_infer_schema_length(options.infer_schema_length)
+        .with_dtype_overwrite(dtypes)
+        .finish()?;
+    Ok(frame)
+}
+
+fn ascii_byte(c: char, name: &str) -> Result<u8, PoldaError> {
+    if c.is_ascii() {
+        Ok(c as u8)
+    } else {
+        Err(PoldaError::QueryError(format!("CSV {} must be an ASCII character", name)))
+    }
+}
+
+/// Lazily scan an Arrow IPC file.
+pub fn scan_ipc(path: &Path) -> Result<LazyFrame, PoldaError> {
+    Ok(LazyFrame::scan_ipc(path, ScanArgsIpc::default())?)
+}
+
+/// Lazily scan a newline delimited JSON file.
+pub fn scan_json(path: &Path) -> Result<LazyFrame, PoldaError> {
+    Ok(LazyJsonLineReader::new(path.to_string_lossy().to_string()).finish()?)
+}
+
+/// Lazily scan a Parquet file.
+pub fn scan_parquet(path: &Path) -> Result<LazyFrame, PoldaError> {
+    Ok(LazyFrame::scan_parquet(path, ScanArgsParquet::default())?)
 }
 
 fn parse_constant_expr(
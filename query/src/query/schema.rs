@@ -1,26 +1,79 @@
-use polars::prelude::CsvReader;
 use polars::prelude::Field;
-use polars::prelude::SerReader;
-use std::collections::HashMap;
+use polars::prelude::LazyFrame;
 use std::collections::HashSet;
 use std::sync::Arc;
 
+use crate::column::Column;
+use crate::context::ExecutionContext;
 use crate::data_type::DataType;
 use crate::doc::Aggregate;
 use crate::doc::AggregateComputation;
 use crate::doc::ComputeOperation;
 use crate::doc::JoinColumn;
+use crate::doc::JoinType;
 use crate::doc::Node;
 use crate::doc::SelectColumn;
 use crate::doc::Value;
 use crate::error::PoldaError;
+use super::duck_db_query::table_schema;
+use super::polars_query::scan_csv;
+use super::polars_query::scan_ipc;
+use super::polars_query::scan_json;
+use super::polars_query::scan_parquet;
 
-#[derive(Debug, Clone)]
-pub struct Schema(pub Arc<HashMap<String, DataType>>);
+#[derive(Debug, Clone, Default)]
+pub struct Schema(pub Arc<Vec<Column>>);
 
 impl Schema {
+    pub fn new(columns: Vec<Column>) -> Schema {
+        Schema(Arc::new(columns))
+    }
+
+    /// The columns in the same order as the result.
+    pub fn columns(&self) -> &[Column] {
+        &self.0
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.get(name).is_some()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&DataType> {
+        self.0
+            .iter()
+            .find(|column| column.name == name)
+            .map(|column| &column.data_type)
+    }
+
+    /// Replace the data type of column `name` or append the column if it
+    /// doesn't exist, like `LazyFrame::with_column`.
+    pub fn insert(&mut self, name: String, data_type: DataType) {
+        let columns = Arc::make_mut(&mut self.0);
+        if let Some(column) = columns.iter_mut().find(|column| column.name == name) {
+            column.data_type = data_type;
+        } else {
+            columns.push(Column { name, data_type });
+        }
+    }
+
+    /// Get the schema of a lazy frame without collecting it.  Used for file
+    /// formats that store their schema, e.g. Parquet and IPC.
+    pub fn try_from_frame(frame: &LazyFrame) -> Result<Schema, PoldaError> {
+        let mut columns = vec![];
+        for field in frame.schema()?.iter_fields() {
+            let Field { name, dtype } = field;
+            let data_type = DataType::try_from(dtype)?;
+            columns.push(Column { name, data_type });
+        }
+        Ok(Schema::new(columns))
+    }
+
     /// Validate node and return `Schema`.
-    pub fn try_from_node(node: &Node, inputs: Vec<Schema>) -> Result<Schema, PoldaError> {
+    pub fn try_from_node(
+        node: &Node,
+        inputs: Vec<Schema>,
+        context: &ExecutionContext
+    ) -> Result<Schema, PoldaError> {
         match node {
             Node::Aggregate {
                 id: _,
@@ -33,8 +86,10 @@ impl Schema {
                     return Err(PoldaError::QueryError(format!("AggregateNode is missing an input node")));
                 }
 
-                let schema = inputs[0].clone().0;
-                let mut new_schema = HashMap::new();
+                let schema = &inputs[0];
+                // Group columns come first like in the result of a group by.
+                let mut groups = vec![];
+                let mut aggs = vec![];
 
                 for agg in aggregates.iter() {
                     let Aggregate { column, computation, alias } = agg;
@@ -52,13 +107,23 @@ impl Schema {
                     } else {
                         alias
                     };
-                    if new_schema.contains_key(new_column) {
+                    let exists = groups
+                        .iter()
+                        .chain(aggs.iter())
+                        .any(|c: &Column| &c.name == new_column);
+                    if exists {
                         return Err(PoldaError::QueryError(format!("Found duplicate columns \"{}\"", new_column)));
                     }
-                    new_schema.insert(new_column.clone(), dtype);
+                    let new_column = Column { name: new_column.clone(), data_type: dtype };
+                    if let AggregateComputation::Group = computation {
+                        groups.push(new_column);
+                    } else {
+                        aggs.push(new_column);
+                    }
                 }
 
-                Ok(Schema(Arc::new(new_schema)))
+                groups.append(&mut aggs);
+                Ok(Schema::new(groups))
             }
 
             Node::Bins {
@@ -67,24 +132,37 @@ impl Schema {
                 input: _,
                 name,
                 column,
-                lower_bound: _,
-                upper_bound: _,
-                count: _,
+                lower_bound,
+                upper_bound,
+                count,
                 outputs: _
             } => {
                 if inputs.len() < 1 {
                     return Err(PoldaError::QueryError(format!("BinsNode is missing an input table")));
                 }
 
-                let mut schema = inputs[0].clone().0.as_ref().clone();
+                let mut schema = inputs[0].clone();
 
                 let dtype = schema
                     .get(column)
-                    .ok_or(PoldaError::QueryError(format!("Column \"{}\" doesn't exist", column)))?
-                    .clone();
-                schema.insert(name.clone(), dtype);
+                    .ok_or(PoldaError::QueryError(format!("Column \"{}\" doesn't exist", column)))?;
+                if !dtype.is_numeric() {
+                    return Err(PoldaError::QueryError(format!("Column \"{}\" is not a number", column)));
+                }
+                if *count == 0 || *count > u32::MAX as usize {
+                    return Err(PoldaError::QueryError(format!("Bins count must be between 1 and {}", u32::MAX)));
+                }
+                if !lower_bound.is_finite() || !upper_bound.is_finite() {
+                    return Err(PoldaError::QueryError(format!("Bins bounds must be finite numbers")));
+                }
+                if lower_bound >= upper_bound {
+                    return Err(PoldaError::QueryError(format!("Bins lower bound must be less than the upper bound")));
+                }
+
+                // The output is the bucket index.
+                schema.insert(name.clone(), DataType::UInt32);
 
-                Ok(Schema(Arc::new(schema)))
+                Ok(schema)
             }
 
             Node::Case {
@@ -101,7 +179,7 @@ impl Schema {
                     return Err(PoldaError::QueryError(format!("CaseNode is missing an input table")));
                 }
 
-                let mut schema = inputs[0].clone().0.as_ref().clone();
+                let mut schema = inputs[0].clone();
 
                 // Validate cases.
                 for case in cases.iter() {
@@ -124,7 +202,7 @@ impl Schema {
 
                 schema.insert(name.clone(), data_type.clone());
 
-                Ok(Schema(Arc::new(schema)))
+                Ok(schema)
             }
 
             Node::Cast {
@@ -140,15 +218,15 @@ impl Schema {
                     return Err(PoldaError::QueryError(format!("CastNode is missing an input table")));
                 }
 
-                let mut schema = inputs[0].clone().0.as_ref().clone();
+                let mut schema = inputs[0].clone();
 
-                if !schema.contains_key(column) {
+                if !schema.contains(column) {
                     return Err(PoldaError::QueryError(format!("Column \"{}\" doesn't exist", column)));
                 }
 
                 schema.insert(name.clone(), data_type.clone());
 
-                Ok(Schema(Arc::new(schema)))
+                Ok(schema)
             }
 
             Node::Compute {
@@ -164,7 +242,7 @@ impl Schema {
                     return Err(PoldaError::QueryError(format!("CastNode is missing an input table")));
                 }
 
-                let mut schema = inputs[0].clone().0.as_ref().clone();
+                let mut schema = inputs[0].clone();
 
                 macro_rules! insert_if_exists {
                     ($schema:ident, $col:ident) => {{
@@ -178,7 +256,7 @@ impl Schema {
 
                 macro_rules! insert_dtype_if_exists {
                     ($schema:ident, $col:ident, $dtype:expr) => {
-                        if !schema.contains_key(column) {
+                        if !schema.contains(column) {
                             return Err(PoldaError::QueryError(format!("Column \"{}\" doesn't exist", column)));
                         }
                         schema.insert(name.clone(), $dtype);
@@ -292,7 +370,7 @@ impl Schema {
                     }
                 }
 
-                Ok(Schema(Arc::new(schema)))
+                Ok(schema)
             }
 
 
@@ -308,13 +386,13 @@ impl Schema {
                     return Err(PoldaError::QueryError(format!("FilterNode is missing an input table")));
                 }
 
-                let schema = inputs[0].clone().0;
+                let schema = inputs[0].clone();
 
-                if !schema.contains_key(column) {
+                if !schema.contains(column) {
                     return Err(PoldaError::QueryError(format!("Column \"{}\" doesn't exist", column)));
                 }
 
-                Ok(Schema(schema))
+                Ok(schema)
             }
 
             Node::Join {
@@ -322,7 +400,7 @@ impl Schema {
                 position: _,
                 left_input: _,
                 right_input: _,
-                join_type: _,
+                join_type,
                 columns,
                 outputs: _
             } => {
@@ -331,8 +409,8 @@ impl Schema {
                 }
 
                 let mut inputs = inputs.into_iter();
-                let left_schema = inputs.next().unwrap().0;
-                let right_schema = inputs.next().unwrap().0;
+                let left_schema = inputs.next().unwrap();
+                let right_schema = inputs.next().unwrap();
                 let mut right_join_columns = HashSet::new();
 
                 for join_column in columns.iter() {
@@ -347,33 +425,70 @@ impl Schema {
                     right_join_columns.insert(right.clone());
                 }
 
-                let mut new_schema = left_schema.as_ref().clone();
+                // Left columns come first followed by the right columns
+                // that aren't join keys.  Name clashes get a "_right" suffix.
+                let mut new_schema = left_schema.columns().to_vec();
 
-                for (column, dtype) in right_schema.iter() {
-                    if !new_schema.contains_key(column) {
-                        new_schema.insert(column.clone(), dtype.clone());
+                for Column { name, data_type } in right_schema.columns().iter() {
+                    if !matches!(join_type, JoinType::Cross) && right_join_columns.contains(name) {
+                        continue;
                     }
+                    let name = if left_schema.contains(name) {
+                        format!("{}_right", name)
+                    } else {
+                        name.clone()
+                    };
+                    new_schema.push(Column { name, data_type: data_type.clone() });
                 }
 
-                Ok(Schema(Arc::new(new_schema)))
+                Ok(Schema::new(new_schema))
             }
 
             Node::LoadCsv {
                 id: _,
                 position: _,
                 filename,
+                options,
                 outputs: _
             } => {
-                let frame = CsvReader::from_path(filename)?
-                    .with_n_rows(Some(100))
-                    .finish()?;
-                let mut schema = HashMap::new();
-                for field in frame.schema().iter_fields() {
-                    let Field { name, dtype } = field;
-                    let dtype = DataType::try_from(dtype)?;
-                    schema.insert(name, dtype);
-                }
-                Ok(Schema(Arc::new(schema)))
+                Schema::try_from_frame(&scan_csv(&context.resolve(filename)?, options)?)
+            }
+
+            Node::LoadIpc {
+                id: _,
+                position: _,
+                filename,
+                outputs: _
+            } => {
+                Schema::try_from_frame(&scan_ipc(&context.resolve(filename)?)?)
+            }
+
+            Node::LoadJson {
+                id: _,
+                position: _,
+                filename,
+                outputs: _
+            } => {
+                Schema::try_from_frame(&scan_json(&context.resolve(filename)?)?)
+            }
+
+            Node::LoadParquet {
+                id: _,
+                position: _,
+                filename,
+                outputs: _
+            } => {
+                Schema::try_from_frame(&scan_parquet(&context.resolve(filename)?)?)
+            }
+
+            Node::LoadDuckDb {
+                id: _,
+                position: _,
+                filename,
+                table,
+                outputs: _
+            } => {
+                table_schema(&context.resolve(filename)?, table)
             }
 
             Node::Select {
@@ -387,8 +502,8 @@ impl Schema {
                     return Err(PoldaError::QueryError(format!("SelectNode is missing an input table")));
                 }
 
-                let schema = inputs[0].clone().0;
-                let mut new_schema = HashMap::new();
+                let schema = &inputs[0];
+                let mut new_schema = Schema::default();
 
                 for column in columns.iter() {
                     let SelectColumn { column, alias } = column;
@@ -399,13 +514,13 @@ impl Schema {
                     } else {
                         column
                     };
-                    if new_schema.contains_key(new_column) {
+                    if new_schema.contains(new_column) {
                         return Err(PoldaError::QueryError(format!("Found duplicate columns \"{}\" in SelectNode", new_column)));
                     }
                     new_schema.insert(new_column.clone(), dtype.clone());
                 }
 
-                Ok(Schema(Arc::new(new_schema)))
+                Ok(new_schema)
             }
 
             Node::Sort {
@@ -423,7 +538,7 @@ impl Schema {
 
                 for sorter in sorters.iter() {
                     let column = &sorter.column;
-                    if !schema.0.contains_key(column) {
+                    if !schema.contains(column) {
                         return Err(PoldaError::QueryError(format!("Column \"{}\" doesn't exist", column)));
                     }
                 }
@@ -442,10 +557,10 @@ impl Schema {
                 }
 
                 let mut inputs = inputs.into_iter();
-                let primary_schema = inputs.next().unwrap().0;
-                let secondary_schema = inputs.next().unwrap().0;
+                let primary_schema = inputs.next().unwrap();
+                let secondary_schema = inputs.next().unwrap();
 
-                for (sec_col, sec_type) in secondary_schema.iter() {
+                for Column { name: sec_col, data_type: sec_type } in secondary_schema.columns().iter() {
                     let pri_type = primary_schema.get(sec_col)
                         .ok_or(PoldaError::QueryError(format!("Column \"{}\" is missing in the first input table", sec_col)))?;
                     if pri_type != sec_type {
@@ -453,13 +568,36 @@ impl Schema {
                     }
                 }
 
-                for (pri_col, _) in primary_schema.iter() {
-                    if !secondary_schema.contains_key(pri_col) {
+                for Column { name: pri_col, data_type: _ } in primary_schema.columns().iter() {
+                    if !secondary_schema.contains(pri_col) {
                         return Err(PoldaError::QueryError(format!("Column \"{}\" is missing in the second input table", pri_col)));
                     }
                 }
 
-                Ok(Schema(primary_schema))
+                Ok(primary_schema)
+            }
+
+            // Sinks pass their input through.
+            Node::WriteCsv {
+                id: _,
+                position: _,
+                input: _,
+                filename: _,
+                mode: _,
+                outputs: _
+            } | Node::WriteParquet {
+                id: _,
+                position: _,
+                input: _,
+                filename: _,
+                mode: _,
+                compression: _,
+                outputs: _
+            } => {
+                if inputs.len() < 1 {
+                    return Err(PoldaError::QueryError(format!("SinkNode is missing an input table")));
+                }
+                Ok(inputs[0].clone())
             }
         }
     }
@@ -0,0 +1,1068 @@
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::path::Path;
+
+use crate::context::ExecutionContext;
+use crate::data_type::DataType;
+use crate::doc::Aggregate;
+use crate::doc::AggregateComputation;
+use crate::doc::Case;
+use crate::doc::ComputeOperation;
+use crate::doc::CsvOptions;
+use crate::doc::FilterPredicate;
+use crate::doc::JoinColumn;
+use crate::doc::JoinType;
+use crate::doc::Node;
+use crate::doc::SelectColumn;
+use crate::doc::SortDirection;
+use crate::doc::Sorter;
+use crate::doc::Value;
+use crate::error::PoldaError;
+use super::Schema;
+use super::SqlQuery;
+
+/// The SQL flavor to emit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Dialect {
+    Ansi,
+    DuckDb,
+    Sqlite
+}
+
+/// A table a node reads from: the name of the CTE that produces it and its
+/// schema.
+pub struct SqlInput<'a> {
+    pub name: &'a str,
+    pub schema: &'a Schema
+}
+
+/// Compile node `id` and its dependencies into a single SQL statement with one
+/// CTE per node.  `nodes` is typically the output of `Doc::extract_nodes`.
+///
+/// DuckDB reads the source files directly.  The other dialects don't have a
+/// standard way to read files, so a source node refers to a table named
+/// after the file stem, e.g. `data/sales.csv` becomes `"sales"`.
+pub fn compile_sql(
+    nodes: &HashMap<String, Node>,
+    id: &String,
+    dialect: Dialect,
+    context: &ExecutionContext
+) -> Result<String, PoldaError> {
+    let mut schemas = HashMap::new();
+    let mut ctes = vec![];
+    let mut visiting = HashSet::new();
+    compile_node(nodes, id, dialect, context, &mut visiting, &mut schemas, &mut ctes)?;
+
+    let ctes: Vec<String> = ctes
+        .iter()
+        .map(|SqlQuery { id, query }| format!("{} AS (\n    {}\n)", quote_ident(id), query))
+        .collect();
+    Ok(format!("WITH {}\nSELECT * FROM {}", ctes.join(",\n"), quote_ident(id)))
+}
+
+/// Compile the inputs of a node, then the node itself.  The CTEs are pushed
+/// in dependency order.
+fn compile_node(
+    nodes: &HashMap<String, Node>,
+    id: &String,
+    dialect: Dialect,
+    context: &ExecutionContext,
+    visiting: &mut HashSet<String>,
+    schemas: &mut HashMap<String, Schema>,
+    ctes: &mut Vec<SqlQuery>
+) -> Result<(), PoldaError> {
+    if schemas.contains_key(id) {
+        return Ok(());
+    }
+    if !visiting.insert(id.clone()) {
+        return Err(PoldaError::QueryError(format!("Node \"{}\" depends on itself", id)));
+    }
+
+    let node = nodes
+        .get(id)
+        .ok_or(PoldaError::DocError(format!("Node with id \"{}\" doesn't exist", id)))?;
+
+    let mut input_ids = vec![];
+    for input in node.inputs() {
+        let input = input
+            .as_ref()
+            .ok_or(PoldaError::QueryError(format!("Node {} is missing an input", id)))?;
+        compile_node(nodes, input, dialect, context, visiting, schemas, ctes)?;
+        input_ids.push(input);
+    }
+
+    let input_schemas = input_ids
+        .iter()
+        .map(|input| schemas.get(*input).unwrap().clone())
+        .collect();
+    let schema = Schema::try_from_node(node, input_schemas, context)?;
+    let inputs: Vec<SqlInput> = input_ids
+        .iter()
+        .map(|input| SqlInput { name: input, schema: schemas.get(*input).unwrap() })
+        .collect();
+    let query = node_to_sql(node, &inputs, &schema, dialect)?;
+
+    ctes.push(SqlQuery { id: id.clone(), query });
+    schemas.insert(id.clone(), schema);
+    visiting.remove(id);
+    Ok(())
+}
+
+/// Translate a single node into a `SELECT` statement that reads from the
+/// CTEs of its inputs.  `schema` is the output schema of the node.
+pub fn node_to_sql(
+    node: &Node,
+    inputs: &[SqlInput],
+    schema: &Schema,
+    dialect: Dialect
+) -> Result<String, PoldaError> {
+    let sql = match node {
+        Node::Aggregate {
+            id: _,
+            position: _,
+            input: _,
+            aggregates,
+            outputs: _
+        } => {
+            let input = first_input(node, inputs)?;
+            let mut exprs = vec![];
+            let mut groups = vec![];
+
+            for agg in aggregates.iter() {
+                let Aggregate { column, computation, alias } = agg;
+                let column_ident = quote_ident(column);
+                use AggregateComputation::*;
+                let expr = match computation {
+                    Count => String::from("COUNT(*)"),
+                    First => {
+                        require_duck_db(dialect, "First aggregate")?;
+                        format!("FIRST({})", column_ident)
+                    }
+                    Group => {
+                        groups.push(column_ident.clone());
+                        column_ident
+                    }
+                    Last => {
+                        require_duck_db(dialect, "Last aggregate")?;
+                        format!("LAST({})", column_ident)
+                    }
+                    Max => format!("MAX({})", column_ident),
+                    Mean => format!("AVG({})", column_ident),
+                    Median => median(&column_ident, dialect)?,
+                    Min => format!("MIN({})", column_ident),
+                    Sum => format!("SUM({})", column_ident)
+                };
+                let name = if alias.is_empty() {
+                    column
+                } else {
+                    alias
+                };
+                exprs.push(format!("{} AS {}", expr, quote_ident(name)));
+            }
+
+            if exprs.is_empty() {
+                return Err(PoldaError::QueryError(format!("AggregateNode has no aggregates")));
+            }
+
+            let mut sql = format!(
+                "SELECT {} FROM {}",
+                exprs.join(", "),
+                quote_ident(input.name)
+            );
+            if !groups.is_empty() {
+                sql.push_str(&format!(" GROUP BY {}", groups.join(", ")));
+            }
+            sql
+        }
+
+        Node::Bins {
+            id: _,
+            position: _,
+            input: _,
+            name,
+            column,
+            lower_bound,
+            upper_bound,
+            count,
+            outputs: _
+        } => {
+            // Same buckets as the Polars backend.  Rounding can put a value
+            // just below the upper bound into bucket `count`, so the index is
+            // clamped to the last bucket.
+            let input = first_input(node, inputs)?;
+            let column_ident = quote_ident(column);
+            let width = (upper_bound - lower_bound) / *count as f64;
+            let index = format!("FLOOR(({} - {}) / {})", column_ident, lower_bound, width);
+            let expr = format!(
+                "CASE WHEN {c} < {lower} OR {c} > {upper} THEN NULL ELSE CAST({index} AS {dtype}) END",
+                c = column_ident,
+                lower = lower_bound,
+                upper = upper_bound,
+                index = least(&index, &(count - 1).to_string(), dialect),
+                dtype = data_type_to_sql(&DataType::UInt32, dialect)?
+            );
+            with_column(input, name, &expr)
+        }
+
+        Node::Case {
+            id: _,
+            position: _,
+            input: _,
+            name,
+            data_type,
+            cases,
+            default,
+            outputs: _
+        } => {
+            let input = first_input(node, inputs)?;
+            let default = value_to_sql(default, data_type, dialect)?;
+            let expr = if cases.is_empty() {
+                default
+            } else {
+                let mut expr = String::from("CASE");
+                for Case { column, value } in cases.iter() {
+                    expr.push_str(&format!(
+                        " WHEN {} THEN {}",
+                        quote_ident(column),
+                        value_to_sql(value, data_type, dialect)?
+                    ));
+                }
+                expr.push_str(&format!(" ELSE {} END", default));
+                expr
+            };
+            with_column(input, name, &expr)
+        }
+
+        Node::Cast {
+            id: _,
+            position: _,
+            input: _,
+            name,
+            column,
+            data_type,
+            outputs: _
+        } => {
+            let input = first_input(node, inputs)?;
+            let expr = format!(
+                "CAST({} AS {})",
+                quote_ident(column),
+                data_type_to_sql(data_type, dialect)?
+            );
+            with_column(input, name, &expr)
+        }
+
+        Node::Compute {
+            id: _,
+            position: _,
+            input: _,
+            name,
+            column,
+            operation,
+            outputs: _
+        } => {
+            let input = first_input(node, inputs)?;
+            let column_ident = quote_ident(column);
+            // Column is guaranteed to exists by the Schema builder.
+            let dtype = input.schema.get(column).unwrap();
+
+            use ComputeOperation::*;
+            let expr = match operation {
+                Add(v) => format!("({} + {})", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                Subtract(v) => format!("({} - {})", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                Multiply(v) => format!("({} * {})", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                Divide(v) => format!("({} / {})", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                IsEqualTo(v) => format!("({} = {})", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                IsNotEqualTo(v) => format!("({} <> {})", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                IsLessThan(v) => format!("({} < {})", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                IsLessThanEqual(v) => format!("({} <= {})", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                IsGreaterThan(v) => format!("({} > {})", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                IsGreaterThanEqual(v) => format!("({} >= {})", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                IsNull => format!("({} IS NULL)", column_ident),
+
+                IsNotNull => format!("({} IS NOT NULL)", column_ident),
+
+                And(v) => format!("({} AND {})", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                Or(v) => format!("({} OR {})", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                Xor(v) => format!("({} <> {})", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                // Aggregates are broadcasted to every row like in Polars.
+                Mean => format!("AVG({}) OVER ()", column_ident),
+
+                Median => {
+                    require_duck_db(dialect, "Median of a column")?;
+                    format!("MEDIAN({}) OVER ()", column_ident)
+                }
+
+                Min => format!("MIN({}) OVER ()", column_ident),
+
+                Max => format!("MAX({}) OVER ()", column_ident),
+            };
+            with_column(input, name, &expr)
+        }
+
+        Node::Filter {
+            id: _,
+            position: _,
+            input: _,
+            column,
+            predicate,
+            outputs: _
+        } => {
+            let input = first_input(node, inputs)?;
+            let column_ident = quote_ident(column);
+            let dtype = schema.get(column).unwrap();
+
+            use FilterPredicate::*;
+            let predicate = match predicate {
+                IsEqualTo(v) => format!("{} = {}", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                IsNotEqualTo(v) => format!("{} <> {}", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                IsLessThan(v) => format!("{} < {}", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                IsLessThanEqual(v) => format!("{} <= {}", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                IsGreaterThan(v) => format!("{} > {}", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                IsGreaterThanEqual(v) => format!("{} >= {}", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                IsNull => format!("{} IS NULL", column_ident),
+
+                IsNotNull => format!("{} IS NOT NULL", column_ident),
+
+                And(v) => format!("{} AND {}", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                Or(v) => format!("{} OR {}", column_ident, value_to_sql(v, dtype, dialect)?),
+
+                Xor(v) => format!("{} <> {}", column_ident, value_to_sql(v, dtype, dialect)?),
+            };
+
+            format!("SELECT * FROM {} WHERE {}", quote_ident(input.name), predicate)
+        }
+
+        Node::Join {
+            id: _,
+            position: _,
+            left_input: _,
+            right_input: _,
+            join_type,
+            columns,
+            outputs: _
+        } => {
+            if inputs.len() < 2 {
+                return Err(PoldaError::QueryError(format!("JoinNode is missing an input table")));
+            }
+            let left = &inputs[0];
+            let right = &inputs[1];
+
+            // Same layout as the schema: the left columns followed by the
+            // right columns that aren't join keys.  Keys of a right or full
+            // join are coalesced since the left side may be null.
+            let mut right_keys = HashMap::new();
+            if !matches!(join_type, JoinType::Cross) {
+                for JoinColumn { left, right } in columns.iter() {
+                    right_keys.insert(right, left);
+                }
+            }
+            let coalesce = matches!(join_type, JoinType::Right | JoinType::Full);
+            let mut exprs = vec![];
+            for column in left.schema.columns() {
+                let right_key = right_keys
+                    .iter()
+                    .find(|(_, left)| **left == &column.name)
+                    .map(|(right, _)| *right);
+                match right_key {
+                    Some(right_key) if coalesce => exprs.push(format!(
+                        "COALESCE(l.{}, r.{}) AS {}",
+                        quote_ident(&column.name),
+                        quote_ident(right_key),
+                        quote_ident(&column.name)
+                    )),
+                    _ => exprs.push(format!("l.{}", quote_ident(&column.name)))
+                }
+            }
+            for column in right.schema.columns() {
+                if right_keys.contains_key(&column.name) {
+                    continue;
+                }
+                if left.schema.contains(&column.name) {
+                    exprs.push(format!(
+                        "r.{} AS {}",
+                        quote_ident(&column.name),
+                        quote_ident(&format!("{}_right", column.name))
+                    ));
+                } else {
+                    exprs.push(format!("r.{}", quote_ident(&column.name)));
+                }
+            }
+
+            let join = match join_type {
+                JoinType::Inner => "INNER JOIN",
+                JoinType::Left => "LEFT JOIN",
+                JoinType::Right => "RIGHT JOIN",
+                JoinType::Full => "FULL OUTER JOIN",
+                JoinType::Cross => "CROSS JOIN"
+            };
+
+            let mut sql = format!(
+                "SELECT {} FROM {} AS l {} {} AS r",
+                exprs.join(", "),
+                quote_ident(left.name),
+                join,
+                quote_ident(right.name)
+            );
+
+            if let JoinType::Cross = join_type {
+                // Cross join doesn't take join columns.
+            } else {
+                if columns.is_empty() {
+                    return Err(PoldaError::QueryError(format!("JoinNode has no join columns")));
+                }
+                let conditions: Vec<String> = columns
+                    .iter()
+                    .map(|JoinColumn { left, right }| {
+                        format!("l.{} = r.{}", quote_ident(left), quote_ident(right))
+                    })
+                    .collect();
+                sql.push_str(&format!(" ON {}", conditions.join(" AND ")));
+            }
+            sql
+        }
+
+        Node::LoadCsv {
+            id: _,
+            position: _,
+            filename,
+            options,
+            outputs: _
+        } => {
+            if let Dialect::DuckDb = dialect {
+                read_csv_duck_db(filename, options)?
+            } else {
+                read_file(filename, "", dialect)?
+            }
+        }
+
+        Node::LoadIpc {
+            id: _,
+            position: _,
+            filename,
+            outputs: _
+        } => {
+            if let Dialect::DuckDb = dialect {
+                return Err(PoldaError::QueryError(format!("DuckDB can't read IPC file \"{}\"", filename)));
+            }
+            read_file(filename, "", dialect)?
+        }
+
+        Node::LoadJson {
+            id: _,
+            position: _,
+            filename,
+            outputs: _
+        } => {
+            read_file(filename, "read_ndjson_auto", dialect)?
+        }
+
+        Node::LoadParquet {
+            id: _,
+            position: _,
+            filename,
+            outputs: _
+        } => {
+            read_file(filename, "read_parquet", dialect)?
+        }
+
+        Node::LoadDuckDb {
+            id: _,
+            position: _,
+            filename: _,
+            table,
+            outputs: _
+        } => {
+            format!("SELECT * FROM {}", quote_ident(table))
+        }
+
+        Node::Select {
+            id: _,
+            position: _,
+            input: _,
+            columns,
+            outputs: _
+        } => {
+            let input = first_input(node, inputs)?;
+            if columns.is_empty() {
+                return Err(PoldaError::QueryError(format!("SelectNode has no columns")));
+            }
+            let exprs: Vec<String> = columns
+                .iter()
+                .map(|SelectColumn { column, alias }| {
+                    if alias.is_empty() {
+                        quote_ident(column)
+                    } else {
+                        format!("{} AS {}", quote_ident(column), quote_ident(alias))
+                    }
+                })
+                .collect();
+            format!("SELECT {} FROM {}", exprs.join(", "), quote_ident(input.name))
+        }
+
+        Node::Sort {
+            id: _,
+            position: _,
+            input: _,
+            sorters,
+            outputs: _
+        } => {
+            let input = first_input(node, inputs)?;
+            let mut sql = format!("SELECT * FROM {}", quote_ident(input.name));
+            if !sorters.is_empty() {
+                let exprs: Vec<String> = sorters
+                    .iter()
+                    .map(|Sorter { column, direction }| {
+                        let direction = if let SortDirection::Desc = direction {
+                            "DESC"
+                        } else {
+                            "ASC"
+                        };
+                        format!("{} {} NULLS LAST", quote_ident(column), direction)
+                    })
+                    .collect();
+                sql.push_str(&format!(" ORDER BY {}", exprs.join(", ")));
+            }
+            sql
+        }
+
+        Node::Union {
+            id: _,
+            position: _,
+            primary_input: _,
+            secondary_input: _,
+            outputs: _
+        } => {
+            if inputs.len() < 2 {
+                return Err(PoldaError::QueryError(format!("UnionNode is missing an input table")));
+            }
+            // Select the columns explicitly so that both sides have the same
+            // column order.
+            let columns: Vec<String> = schema
+                .columns()
+                .iter()
+                .map(|column| quote_ident(&column.name))
+                .collect();
+            let columns = columns.join(", ");
+            format!(
+                "SELECT {} FROM {} UNION ALL SELECT {} FROM {}",
+                columns,
+                quote_ident(inputs[0].name),
+                columns,
+                quote_ident(inputs[1].name)
+            )
+        }
+
+        Node::WriteCsv {
+            id: _,
+            position: _,
+            input: _,
+            filename: _,
+            mode: _,
+            outputs: _
+        } | Node::WriteParquet {
+            id: _,
+            position: _,
+            input: _,
+            filename: _,
+            mode: _,
+            compression: _,
+            outputs: _
+        } => {
+            let input = first_input(node, inputs)?;
+            format!("SELECT * FROM {}", quote_ident(input.name))
+        }
+    };
+
+    Ok(sql)
+}
+
+/// Map a DuckDB column type name into `DataType`.
+pub fn data_type_from_sql(name: &str) -> Result<DataType, PoldaError> {
+    let name = name.trim().to_uppercase();
+    if let Some(inner) = name.strip_suffix("[]") {
+        return Ok(DataType::List(Box::new(data_type_from_sql(inner)?)));
+    }
+    if name.starts_with("DECIMAL") || name.starts_with("NUMERIC") {
+        return Ok(DataType::Float64);
+    }
+    let dtype = match name.as_str() {
+        "BOOLEAN" | "BOOL" => DataType::Boolean,
+        "DATE" => DataType::Date,
+        "TIMESTAMP" | "DATETIME" => DataType::DateTime,
+        "INTERVAL" => DataType::Duration,
+        "REAL" | "FLOAT" | "FLOAT4" => DataType::Float32,
+        "DOUBLE" | "FLOAT8" => DataType::Float64,
+        "TINYINT" | "INT1" => DataType::Int8,
+        "SMALLINT" | "INT2" => DataType::Int16,
+        "INTEGER" | "INT" | "INT4" => DataType::Int32,
+        "BIGINT" | "INT8" => DataType::Int64,
+        "TIME" => DataType::Time,
+        "UTINYINT" => DataType::UInt8,
+        "USMALLINT" => DataType::UInt16,
+        "UINTEGER" => DataType::UInt32,
+        "UBIGINT" => DataType::UInt64,
+        "VARCHAR" | "TEXT" | "STRING" => DataType::Utf8,
+        _ => return Err(PoldaError::QueryError(format!("Unsupported column type \"{}\"", name)))
+    };
+    Ok(dtype)
+}
+
+pub fn data_type_to_sql(dtype: &DataType, dialect: Dialect) -> Result<String, PoldaError> {
+    let name = match dialect {
+        Dialect::DuckDb => match dtype {
+            DataType::Boolean => "BOOLEAN",
+            DataType::Date => "DATE",
+            DataType::DateTime => "TIMESTAMP",
+            DataType::Duration => "INTERVAL",
+            DataType::Float32 => "REAL",
+            DataType::Float64 => "DOUBLE",
+            DataType::Int8 => "TINYINT",
+            DataType::Int16 => "SMALLINT",
+            DataType::Int32 => "INTEGER",
+            DataType::Int64 => "BIGINT",
+            DataType::List(dtype) => return Ok(format!("{}[]", data_type_to_sql(dtype, dialect)?)),
+            DataType::Time => "TIME",
+            DataType::UInt8 => "UTINYINT",
+            DataType::UInt16 => "USMALLINT",
+            DataType::UInt32 => "UINTEGER",
+            DataType::UInt64 => "UBIGINT",
+            DataType::Utf8 => "VARCHAR"
+        },
+
+        // Standard SQL doesn't have unsigned integers, so use the next wider
+        // signed type.
+        Dialect::Ansi => match dtype {
+            DataType::Boolean => "BOOLEAN",
+            DataType::Date => "DATE",
+            DataType::DateTime => "TIMESTAMP",
+            DataType::Duration => "INTERVAL",
+            DataType::Float32 => "REAL",
+            DataType::Float64 => "DOUBLE PRECISION",
+            DataType::Int8 | DataType::Int16 | DataType::UInt8 => "SMALLINT",
+            DataType::Int32 | DataType::UInt16 => "INTEGER",
+            DataType::Int64 | DataType::UInt32 => "BIGINT",
+            DataType::UInt64 => "NUMERIC(20)",
+            DataType::Time => "TIME",
+            DataType::Utf8 => "VARCHAR",
+            DataType::List(_) => {
+                return Err(PoldaError::QueryError(format!("List type is not supported by ANSI SQL")));
+            }
+        },
+
+        // SQLite only has a few storage classes.  Dates and times are
+        // stored as ISO 8601 strings.
+        Dialect::Sqlite => match dtype {
+            DataType::Boolean
+                | DataType::Int8
+                | DataType::Int16
+                | DataType::Int32
+                | DataType::Int64
+                | DataType::UInt8
+                | DataType::UInt16
+                | DataType::UInt32
+                | DataType::UInt64 => "INTEGER",
+            DataType::Float32 | DataType::Float64 => "REAL",
+            DataType::Date
+                | DataType::DateTime
+                | DataType::Time
+                | DataType::Utf8 => "TEXT",
+            DataType::Duration | DataType::List(_) => {
+                return Err(PoldaError::QueryError(format!("{:?} type is not supported by SQLite", dtype)));
+            }
+        }
+    };
+    Ok(String::from(name))
+}
+
+/// Quote an identifier, e.g. a column or a CTE name.
+pub fn quote_ident(ident: &str) -> String {
+    format!("\"{}\"", ident.replace('"', "\"\""))
+}
+
+/// Quote a string literal.
+pub fn quote_str(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+/// Read a CSV file in DuckDB with the same options as the Polars backend.
+fn read_csv_duck_db(filename: &str, options: &CsvOptions) -> Result<String, PoldaError> {
+    let mut args = vec![
+        quote_str(filename),
+        format!("delim={}", quote_str(&options.delimiter.to_string())),
+        format!("header={}", options.has_header),
+        format!("skip={}", options.skip_rows)
+    ];
+    if let Some(quote_char) = options.quote_char {
+        args.push(format!("quote={}", quote_str(&quote_char.to_string())));
+    }
+    match options.null_values.as_slice() {
+        [] => {}
+        [null_value] => args.push(format!("nullstr={}", quote_str(null_value))),
+        _ => {
+            return Err(PoldaError::QueryError(format!("DuckDB only supports a single CSV null value")));
+        }
+    }
+    match options.infer_schema_length {
+        Some(n) => args.push(format!("sample_size={}", n)),
+        None => args.push(String::from("sample_size=-1"))
+    }
+    let reader = format!("read_csv_auto({})", args.join(", "));
+
+    if options.column_types.is_empty() {
+        return Ok(format!("SELECT * FROM {}", reader));
+    }
+
+    let mut columns: Vec<&String> = options.column_types.keys().collect();
+    columns.sort();
+    let mut casts = vec![];
+    for column in columns {
+        let dtype = data_type_to_sql(&options.column_types[column], Dialect::DuckDb)?;
+        casts.push(format!("CAST({c} AS {t}) AS {c}", c = quote_ident(column), t = dtype));
+    }
+    Ok(format!("SELECT * REPLACE ({}) FROM {}", casts.join(", "), reader))
+}
+
+/// Read a file with a DuckDB table function, or refer to a table named after
+/// the file stem in the other dialects.
+fn read_file(filename: &str, duck_db_function: &str, dialect: Dialect) -> Result<String, PoldaError> {
+    if let Dialect::DuckDb = dialect {
+        Ok(format!("SELECT * FROM {}({})", duck_db_function, quote_str(filename)))
+    } else {
+        let table = Path::new(filename)
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .ok_or(PoldaError::QueryError(format!("Invalid filename \"{}\"", filename)))?;
+        Ok(format!("SELECT * FROM {}", quote_ident(table)))
+    }
+}
+
+fn first_input<'a>(node: &Node, inputs: &'a [SqlInput<'a>]) -> Result<&'a SqlInput<'a>, PoldaError> {
+    inputs
+        .first()
+        .ok_or(PoldaError::QueryError(format!("Node \"{}\" is missing input node", node.id())))
+}
+
+/// Select every column of the input and add (or replace) column `name`.
+fn with_column(input: &SqlInput, name: &str, expr: &str) -> String {
+    let mut exprs = vec![];
+    for column in input.schema.columns() {
+        if column.name == name {
+            exprs.push(format!("{} AS {}", expr, quote_ident(name)));
+        } else {
+            exprs.push(quote_ident(&column.name));
+        }
+    }
+    if !input.schema.contains(name) {
+        exprs.push(format!("{} AS {}", expr, quote_ident(name)));
+    }
+    format!("SELECT {} FROM {}", exprs.join(", "), quote_ident(input.name))
+}
+
+fn parse_constant_sql(
+    constant: &str,
+    dtype: &DataType,
+    dialect: Dialect
+) -> Result<String, PoldaError> {
+    if constant.is_empty() {
+        return Ok(String::from("NULL"));
+    }
+
+    macro_rules! parse_number {
+        ($t:ty, $name:expr) => {
+            constant.parse::<$t>()
+                .map(|constant| constant.to_string())
+                .map_err(|_| PoldaError::ParseError(format!("Can't parse \"{}\" into a {}", constant, $name)))
+        };
+    }
+
+    match dtype {
+        DataType::Boolean => {
+            constant.parse::<bool>()
+                .map(|constant| if constant { String::from("TRUE") } else { String::from("FALSE") })
+                .map_err(|_| PoldaError::ParseError(format!("Can't parse \"{}\" into a Boolean", constant)))
+        }
+
+        DataType::Float32 => parse_number!(f32, "Float32"),
+
+        DataType::Float64 => parse_number!(f64, "Float64"),
+
+        DataType::Int8 => parse_number!(i8, "Int8"),
+
+        DataType::Int16 => parse_number!(i16, "Int16"),
+
+        DataType::Int32 => parse_number!(i32, "Int32"),
+
+        DataType::Int64 => parse_number!(i64, "Int64"),
+
+        DataType::UInt8 => parse_number!(u8, "UInt8"),
+
+        DataType::UInt16 => parse_number!(u16, "UInt16"),
+
+        DataType::UInt32 => parse_number!(u32, "UInt32"),
+
+        DataType::UInt64 => parse_number!(u64, "UInt64"),
+
+        DataType::Utf8 => Ok(quote_str(constant)),
+
+        DataType::Date
+            | DataType::DateTime
+            | DataType::Time => {
+            if let Dialect::Sqlite = dialect {
+                Ok(quote_str(constant))
+            } else {
+                Ok(format!("CAST({} AS {})", quote_str(constant), data_type_to_sql(dtype, dialect)?))
+            }
+        }
+
+        _ => Err(PoldaError::QueryError(format!("Constants of type {:?} are not supported", dtype)))
+    }
+}
+
+fn value_to_sql(value: &Value, dtype: &DataType, dialect: Dialect) -> Result<String, PoldaError> {
+    match value {
+        Value::Column(column) => Ok(quote_ident(column)),
+        Value::Constant(constant) => parse_constant_sql(constant.as_str(), dtype, dialect)
+    }
+}
+
+fn median(column_ident: &str, dialect: Dialect) -> Result<String, PoldaError> {
+    match dialect {
+        Dialect::DuckDb => Ok(format!("MEDIAN({})", column_ident)),
+        Dialect::Ansi => Ok(format!("PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY {})", column_ident)),
+        Dialect::Sqlite => Err(PoldaError::QueryError(format!("Median is not supported by SQLite")))
+    }
+}
+
+/// The smaller of two values.  SQLite's `MIN` takes several arguments
+/// instead of `LEAST`.
+fn least(a: &str, b: &str, dialect: Dialect) -> String {
+    match dialect {
+        Dialect::Sqlite => format!("MIN({}, {})", a, b),
+        Dialect::Ansi | Dialect::DuckDb => format!("LEAST({}, {})", a, b)
+    }
+}
+
+fn require_duck_db(dialect: Dialect, feature: &str) -> Result<(), PoldaError> {
+    if let Dialect::DuckDb = dialect {
+        Ok(())
+    } else {
+        Err(PoldaError::QueryError(format!("{} is not supported by {:?} SQL", feature, dialect)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use crate::column::Column;
+    use crate::doc::Doc;
+    use crate::doc::InputName;
+    use crate::doc::Operation;
+    use crate::doc::Position;
+    use super::*;
+
+    fn schema(columns: &[(&str, DataType)]) -> Schema {
+        let columns = columns
+            .iter()
+            .map(|(name, data_type)| Column { name: name.to_string(), data_type: data_type.clone() })
+            .collect();
+        Schema::new(columns)
+    }
+
+    #[test]
+    fn filter_to_sql() {
+        let input_schema = schema(&[("a", DataType::Int32), ("b", DataType::Utf8)]);
+        let node = Node::Filter {
+            id: "f".to_string(),
+            position: Position { x: 0.0, y: 0.0 },
+            input: Some("x".to_string()),
+            column: "b".to_string(),
+            predicate: FilterPredicate::IsEqualTo(Value::Constant("it's".to_string())),
+            outputs: HashSet::new()
+        };
+        let inputs = [SqlInput { name: "x", schema: &input_schema }];
+        let sql = node_to_sql(&node, &inputs, &input_schema, Dialect::DuckDb).unwrap();
+        assert_eq!(sql, "SELECT * FROM \"x\" WHERE \"b\" = 'it''s'");
+    }
+
+    #[test]
+    fn cast_replaces_column() {
+        let input_schema = schema(&[("a", DataType::Int32), ("b", DataType::Utf8)]);
+        let output_schema = schema(&[("a", DataType::Float64), ("b", DataType::Utf8)]);
+        let node = Node::Cast {
+            id: "c".to_string(),
+            position: Position { x: 0.0, y: 0.0 },
+            input: Some("x".to_string()),
+            name: "a".to_string(),
+            column: "a".to_string(),
+            data_type: DataType::Float64,
+            outputs: HashSet::new()
+        };
+        let inputs = [SqlInput { name: "x", schema: &input_schema }];
+        let sql = node_to_sql(&node, &inputs, &output_schema, Dialect::DuckDb).unwrap();
+        assert_eq!(sql, "SELECT CAST(\"a\" AS DOUBLE) AS \"a\", \"b\" FROM \"x\"");
+    }
+
+    #[test]
+    fn join_to_sql() {
+        let left_schema = schema(&[("id", DataType::Int64), ("name", DataType::Utf8)]);
+        let right_schema = schema(&[("total", DataType::Float64), ("id", DataType::Int64), ("name", DataType::Utf8)]);
+        let node = Node::Join {
+            id: "j".to_string(),
+            position: Position { x: 0.0, y: 0.0 },
+            left_input: Some("l".to_string()),
+            right_input: Some("r".to_string()),
+            join_type: JoinType::Left,
+            columns: vec![JoinColumn { left: "id".to_string(), right: "id".to_string() }],
+            outputs: HashSet::new()
+        };
+        let inputs = [
+            SqlInput { name: "a", schema: &left_schema },
+            SqlInput { name: "b", schema: &right_schema }
+        ];
+        let sql = node_to_sql(&node, &inputs, &left_schema, Dialect::DuckDb).unwrap();
+        assert_eq!(
+            sql,
+            "SELECT l.\"id\", l.\"name\", r.\"total\", r.\"name\" AS \"name_right\" FROM \"a\" AS l LEFT JOIN \"b\" AS r ON l.\"id\" = r.\"id\""
+        );
+    }
+
+    #[test]
+    fn compile_doc() {
+        let position = Position { x: 0.0, y: 0.0 };
+        let set_input = |id: &str, input: &str| Operation::SetInput {
+            id: id.to_string(),
+            name: InputName::Primary,
+            input: Some(input.to_string())
+        };
+        let mut doc = Doc::new();
+        let ops = vec![
+            Operation::InsertNode {
+                node: Node::LoadCsv {
+                    id: "a".to_string(),
+                    position: position.clone(),
+                    filename: "data/supermarket_sales.csv".to_string(),
+                    options: CsvOptions::default(),
+                    outputs: HashSet::new()
+                }
+            },
+            Operation::InsertNode {
+                node: Node::Select {
+                    id: "b".to_string(),
+                    position: position.clone(),
+                    input: None,
+                    columns: vec![
+                        SelectColumn { column: "City".to_string(), alias: "".to_string() },
+                        SelectColumn { column: "Total".to_string(), alias: "".to_string() }
+                    ],
+                    outputs: HashSet::new()
+                }
+            },
+            Operation::InsertNode {
+                node: Node::Filter {
+                    id: "c".to_string(),
+                    position: position.clone(),
+                    input: None,
+                    column: "Total".to_string(),
+                    predicate: FilterPredicate::IsGreaterThan(Value::Constant("100".to_string())),
+                    outputs: HashSet::new()
+                }
+            },
+            Operation::InsertNode {
+                node: Node::Aggregate {
+                    id: "d".to_string(),
+                    position: position.clone(),
+                    input: None,
+                    aggregates: vec![
+                        Aggregate {
+                            column: "City".to_string(),
+                            computation: AggregateComputation::Group,
+                            alias: "".to_string()
+                        },
+                        Aggregate {
+                            column: "Total".to_string(),
+                            computation: AggregateComputation::Sum,
+                            alias: "".to_string()
+                        }
+                    ],
+                    outputs: HashSet::new()
+                }
+            },
+            set_input("b", "a"),
+            set_input("c", "b"),
+            set_input("d", "c")
+        ];
+        doc.execute_operations(ops).unwrap();
+        let nodes = doc.extract_nodes(&"d".to_string()).unwrap();
+        let context = ExecutionContext::default();
+
+        let ctes = concat!(
+            "\"b\" AS (\n    SELECT \"City\", \"Total\" FROM \"a\"\n),\n",
+            "\"c\" AS (\n    SELECT * FROM \"b\" WHERE \"Total\" > 100\n),\n",
+            "\"d\" AS (\n    SELECT \"City\" AS \"City\", SUM(\"Total\") AS \"Total\" FROM \"c\" GROUP BY \"City\"\n)\n",
+            "SELECT * FROM \"d\""
+        );
+        let table = "WITH \"a\" AS (\n    SELECT * FROM \"supermarket_sales\"\n),\n";
+        let file = "WITH \"a\" AS (\n    SELECT * FROM read_csv_auto('data/supermarket_sales.csv', delim=',', header=true, skip=0, quote='\"', sample_size=100)\n),\n";
+        let compile = |dialect| compile_sql(&nodes, &"d".to_string(), dialect, &context).unwrap();
+        assert_eq!(compile(Dialect::Ansi), format!("{}{}", table, ctes));
+        assert_eq!(compile(Dialect::DuckDb), format!("{}{}", file, ctes));
+        assert_eq!(compile(Dialect::Sqlite), format!("{}{}", table, ctes));
+    }
+
+    #[test]
+    fn bins_to_sql() {
+        let input_schema = schema(&[("a", DataType::Float64)]);
+        let output_schema = schema(&[("a", DataType::Float64), ("bin", DataType::UInt32)]);
+        let node = Node::Bins {
+            id: "b".to_string(),
+            position: Position { x: 0.0, y: 0.0 },
+            input: Some("x".to_string()),
+            name: "bin".to_string(),
+            column: "a".to_string(),
+            lower_bound: 0.0,
+            upper_bound: 1.0,
+            count: 3,
+            outputs: HashSet::new()
+        };
+        let inputs = [SqlInput { name: "x", schema: &input_schema }];
+        let sql = node_to_sql(&node, &inputs, &output_schema, Dialect::DuckDb).unwrap();
+        assert_eq!(
+            sql,
+            "SELECT \"a\", CASE WHEN \"a\" < 0 OR \"a\" > 1 THEN NULL ELSE CAST(LEAST(FLOOR((\"a\" - 0) / 0.3333333333333333), 2) AS UINTEGER) END AS \"bin\" FROM \"x\""
+        );
+        let sql = node_to_sql(&node, &inputs, &output_schema, Dialect::Sqlite).unwrap();
+        assert!(sql.contains("CAST(MIN(FLOOR((\"a\" - 0) / 0.3333333333333333), 2) AS INTEGER)"));
+    }
+
+    #[test]
+    fn dialect_specific_sql() {
+        assert_eq!(median("\"a\"", Dialect::Ansi).unwrap(), "PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY \"a\")");
+        assert!(median("\"a\"", Dialect::Sqlite).is_err());
+        assert_eq!(data_type_to_sql(&DataType::UInt32, Dialect::Ansi).unwrap(), "BIGINT");
+        assert_eq!(data_type_to_sql(&DataType::Date, Dialect::Sqlite).unwrap(), "TEXT");
+        assert_eq!(parse_constant_sql("2022-01-01", &DataType::Date, Dialect::Sqlite).unwrap(), "'2022-01-01'");
+    }
+}
@@ -0,0 +1,34 @@
+use serde::Deserialize;
+use serde::Serialize;
+use std::path::Path;
+
+/// File formats that can be loaded by a source node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceFormat {
+    Csv,
+    DuckDb,
+    Ipc,
+    Json,
+    Parquet
+}
+
+impl SourceFormat {
+    /// Guess the format from the file extension.  JSON files are expected to
+    /// be newline delimited.
+    pub fn from_path<T: AsRef<Path>>(path: T) -> Option<SourceFormat> {
+        let ext = path
+            .as_ref()
+            .extension()?
+            .to_str()?
+            .to_lowercase();
+        match ext.as_str() {
+            "csv" => Some(SourceFormat::Csv),
+            "duckdb" => Some(SourceFormat::DuckDb),
+            "arrow" | "feather" | "ipc" => Some(SourceFormat::Ipc),
+            "json" | "jsonl" | "ndjson" => Some(SourceFormat::Json),
+            "parquet" => Some(SourceFormat::Parquet),
+            _ => None
+        }
+    }
+}
@@ -1,5 +1,16 @@
 use polars::frame::DataFrame;
+use polars::prelude::IpcStreamWriter;
+use polars::prelude::SerWriter;
+
+use crate::error::PoldaError;
 
 pub fn collect_json(df: &DataFrame) -> serde_json::Result<String> {
     serde_json::to_string(df)
 }
+
+/// Encode a frame as an Arrow IPC stream.
+pub fn collect_ipc_stream(df: &mut DataFrame) -> Result<Vec<u8>, PoldaError> {
+    let mut buf = vec![];
+    IpcStreamWriter::new(&mut buf).finish(df)?;
+    Ok(buf)
+}
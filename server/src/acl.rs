@@ -0,0 +1,158 @@
+use query::error::PoldaError;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+/// Roles are ordered by what they allow: viewers can read the doc and query
+/// it, editors can also modify it, and owners can also share it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Viewer,
+    Editor,
+    Owner
+}
+
+/// The roles of the users that can access a document, keyed by user id.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Acl {
+    pub roles: BTreeMap<String, Role>
+}
+
+/// The ACL of an open document, shared by the document and the broker.
+pub type SharedAcl = Arc<Mutex<Acl>>;
+
+impl Acl {
+    pub fn role(&self, user_id: &str) -> Option<Role> {
+        self.roles.get(user_id).copied()
+    }
+
+    pub fn allows(&self, user_id: &str, required: Role) -> bool {
+        self.role(user_id).is_some_and(|role| role >= required)
+    }
+
+    pub fn has_owner(&self) -> bool {
+        self.roles.values().any(|role| *role == Role::Owner)
+    }
+
+    /// Give a user a role, or revoke its access with `None`.  A document
+    /// can't be left without an owner.
+    pub fn set_role(&mut self, user_id: String, role: Option<Role>) -> Result<(), PoldaError> {
+        if user_id.is_empty() {
+            return Err(PoldaError::DocError(String::from("User id can't be empty")));
+        }
+        let was_owner = self.role(&user_id) == Some(Role::Owner);
+        match role {
+            Some(role) => self.roles.insert(user_id.clone(), role),
+            None => self.roles.remove(&user_id)
+        };
+        if was_owner && !self.has_owner() {
+            self.roles.insert(user_id, Role::Owner);
+            return Err(PoldaError::DocError(String::from("A document must have an owner")));
+        }
+        Ok(())
+    }
+
+    /// The user that becomes the owner of a doc when it's opened.  A new doc
+    /// is only created with `create` and is owned by its creator.  A doc
+    /// saved before it had an ACL is owned by the admin.
+    pub fn owner_to_claim(
+        &self,
+        path: &str,
+        is_new: bool,
+        create: bool,
+        user_id: &str,
+        admin: Option<&str>
+    ) -> Result<Option<String>, PoldaError> {
+        if is_new {
+            if !create {
+                return Err(PoldaError::DocError(format!("Document \"{}\" doesn't exist", path)));
+            }
+            Ok(Some(user_id.to_string()))
+        } else if !self.has_owner() {
+            match admin {
+                Some(admin) => Ok(Some(admin.to_string())),
+                None => Err(PoldaError::Forbidden(format!("\"{}\" has no owner", path)))
+            }
+        } else {
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn acl(roles: &[(&str, Role)]) -> Acl {
+        Acl {
+            roles: roles
+                .iter()
+                .map(|(user_id, role)| (user_id.to_string(), *role))
+                .collect()
+        }
+    }
+
+    #[test]
+    fn role_order() {
+        assert!(Role::Viewer < Role::Editor);
+        assert!(Role::Editor < Role::Owner);
+
+        let acl = acl(&[("viewer", Role::Viewer), ("editor", Role::Editor), ("owner", Role::Owner)]);
+        assert!(acl.allows("viewer", Role::Viewer));
+        assert!(!acl.allows("viewer", Role::Editor));
+        assert!(acl.allows("editor", Role::Editor));
+        assert!(!acl.allows("editor", Role::Owner));
+        assert!(acl.allows("owner", Role::Viewer));
+        assert!(acl.allows("owner", Role::Owner));
+        assert!(!acl.allows("stranger", Role::Viewer));
+    }
+
+    #[test]
+    fn keep_an_owner() {
+        let mut acl = acl(&[("a", Role::Owner), ("b", Role::Editor)]);
+
+        // The last owner can neither be demoted nor removed.
+        assert!(acl.set_role("a".to_string(), Some(Role::Editor)).is_err());
+        assert_eq!(acl.role("a"), Some(Role::Owner));
+        assert!(acl.set_role("a".to_string(), None).is_err());
+        assert_eq!(acl.role("a"), Some(Role::Owner));
+
+        // Unless there's another owner.
+        acl.set_role("b".to_string(), Some(Role::Owner)).unwrap();
+        acl.set_role("a".to_string(), None).unwrap();
+        assert_eq!(acl.role("a"), None);
+        assert!(acl.set_role("b".to_string(), Some(Role::Viewer)).is_err());
+        assert_eq!(acl.role("b"), Some(Role::Owner));
+
+        assert!(acl.set_role(String::new(), Some(Role::Viewer)).is_err());
+        assert_eq!(acl.roles.len(), 1);
+    }
+
+    #[test]
+    fn claim_owner() {
+        let owned = acl(&[("a", Role::Owner)]);
+        let legacy = Acl::default();
+
+        // New docs.
+        assert_eq!(legacy.owner_to_claim("doc", true, true, "b", Some("admin")).unwrap(), Some("b".to_string()));
+        assert!(matches!(
+            legacy.owner_to_claim("doc", true, false, "b", Some("admin")),
+            Err(PoldaError::DocError(_))
+        ));
+
+        // Docs saved before they had an ACL.
+        assert_eq!(legacy.owner_to_claim("doc", false, true, "b", Some("admin")).unwrap(), Some("admin".to_string()));
+        assert_eq!(legacy.owner_to_claim("doc", false, false, "b", Some("admin")).unwrap(), Some("admin".to_string()));
+        assert!(matches!(
+            legacy.owner_to_claim("doc", false, true, "b", None),
+            Err(PoldaError::Forbidden(_))
+        ));
+
+        // Docs with an owner.
+        assert_eq!(owned.owner_to_claim("doc", false, true, "b", Some("admin")).unwrap(), None);
+        assert_eq!(owned.owner_to_claim("doc", false, false, "b", None).unwrap(), None);
+    }
+}
@@ -0,0 +1,235 @@
+use actix_web::HttpRequest;
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::web;
+use jsonwebtoken::Algorithm;
+use jsonwebtoken::DecodingKey;
+use jsonwebtoken::Validation;
+use query::error::PoldaError;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// The user a client authenticated as.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct User {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>
+}
+
+impl User {
+    /// Every client is this user when authentication is disabled.
+    pub fn anonymous() -> User {
+        User {
+            id: String::from("anonymous"),
+            name: None
+        }
+    }
+}
+
+/// A way to verify the token a client presents.
+pub trait Authenticator: Send + Sync {
+    /// Return `None` if the token isn't known to this authenticator.
+    fn authenticate(&self, token: &str) -> Option<User>;
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenEntry {
+    token: String,
+    user: String,
+    #[serde(default)]
+    name: Option<String>
+}
+
+/// API tokens from a JSON file, e.g.
+/// `[{ "token": "secret", "user": "alice", "name": "Alice" }]`.
+pub struct StaticTokens {
+    tokens: HashMap<String, User>
+}
+
+impl StaticTokens {
+    pub fn load(file: &Path) -> Result<StaticTokens, PoldaError> {
+        let content = fs::read(file)?;
+        let entries: Vec<TokenEntry> = serde_json::from_slice(&content)
+            .map_err(|e| PoldaError::ParseError(format!("Failed to parse \"{}\": {}", file.display(), e)))?;
+        let mut tokens = HashMap::new();
+        for entry in entries.into_iter() {
+            let TokenEntry { token, user, name } = entry;
+            if token.is_empty() {
+                return Err(PoldaError::ParseError(format!("Empty token for user \"{}\" in \"{}\"", user, file.display())));
+            }
+            tokens.insert(token, User { id: user, name });
+        }
+        Ok(StaticTokens { tokens })
+    }
+}
+
+impl Authenticator for StaticTokens {
+    fn authenticate(&self, token: &str) -> Option<User> {
+        self.tokens.get(token).cloned()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    #[serde(default)]
+    name: Option<String>
+}
+
+/// JWTs signed with a shared secret (HS256, HS384 or HS512).  The token must
+/// have a `sub` and an `exp` claim.
+pub struct Jwt {
+    key: DecodingKey,
+    validation: Validation
+}
+
+impl Jwt {
+    pub fn new(secret: &[u8]) -> Jwt {
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.algorithms = vec![Algorithm::HS256, Algorithm::HS384, Algorithm::HS512];
+        Jwt {
+            key: DecodingKey::from_secret(secret),
+            validation
+        }
+    }
+}
+
+impl Authenticator for Jwt {
+    fn authenticate(&self, token: &str) -> Option<User> {
+        let data = jsonwebtoken::decode::<Claims>(token, &self.key, &self.validation).ok()?;
+        let Claims { sub, name } = data.claims;
+        Some(User { id: sub, name })
+    }
+}
+
+/// The authenticators are tried in order.  Without any authenticator every
+/// request is let in as the anonymous user.
+#[derive(Default)]
+pub struct Auth {
+    authenticators: Vec<Box<dyn Authenticator>>
+}
+
+impl Auth {
+    pub fn new() -> Auth {
+        Auth::default()
+    }
+
+    pub fn with(mut self, authenticator: impl Authenticator + 'static) -> Auth {
+        self.authenticators.push(Box::new(authenticator));
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.authenticators.is_empty()
+    }
+
+    /// Authenticate a request by its bearer token.  Browsers can't set
+    /// headers on a websocket, so the token may be a `token` query parameter
+    /// instead.
+    pub fn authenticate(&self, req: &HttpRequest) -> Option<User> {
+        if !self.is_enabled() {
+            return Some(User::anonymous());
+        }
+        let token = request_token(req)?;
+        self.authenticators
+            .iter()
+            .find_map(|authenticator| authenticator.authenticate(&token))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenParams {
+    token: Option<String>
+}
+
+fn request_token(req: &HttpRequest) -> Option<String> {
+    let header = req
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "));
+    if let Some(token) = header {
+        return Some(token.trim().to_string());
+    }
+    web::Query::<TokenParams>::from_query(req.query_string())
+        .ok()
+        .and_then(|params| params.into_inner().token)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+    use jsonwebtoken::EncodingKey;
+    use jsonwebtoken::Header;
+    use std::time::SystemTime;
+    use std::time::UNIX_EPOCH;
+    use super::*;
+
+    #[derive(Serialize)]
+    struct TestClaims {
+        sub: String,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        exp: Option<u64>
+    }
+
+    fn token(secret: &[u8], exp: Option<u64>) -> String {
+        let claims = TestClaims { sub: String::from("alice"), exp };
+        jsonwebtoken::encode(&Header::default(), &claims, &EncodingKey::from_secret(secret)).unwrap()
+    }
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    #[test]
+    fn jwt() {
+        let jwt = Jwt::new(b"secret");
+        let user = jwt.authenticate(&token(b"secret", Some(now() + 3600)));
+        assert_eq!(user, Some(User { id: String::from("alice"), name: None }));
+        // Expired beyond the default leeway of 60 seconds.
+        assert_eq!(jwt.authenticate(&token(b"secret", Some(now() - 3600))), None);
+        assert_eq!(jwt.authenticate(&token(b"other", Some(now() + 3600))), None);
+        assert_eq!(jwt.authenticate(&token(b"secret", None)), None);
+        assert_eq!(jwt.authenticate("not a token"), None);
+    }
+
+    #[test]
+    fn static_tokens() {
+        let file = std::env::temp_dir().join(format!("polda-tokens-{}.json", std::process::id()));
+        fs::write(&file, r#"[{ "token": "t1", "user": "alice", "name": "Alice" }, { "token": "t2", "user": "bob" }]"#).unwrap();
+        let tokens = StaticTokens::load(&file).unwrap();
+        assert_eq!(tokens.authenticate("t1"), Some(User { id: String::from("alice"), name: Some(String::from("Alice")) }));
+        assert_eq!(tokens.authenticate("t2"), Some(User { id: String::from("bob"), name: None }));
+        assert_eq!(tokens.authenticate("t3"), None);
+
+        fs::write(&file, r#"[{ "token": "", "user": "alice" }]"#).unwrap();
+        assert!(StaticTokens::load(&file).is_err());
+        fs::remove_file(&file).unwrap();
+    }
+
+    #[test]
+    fn request_tokens() {
+        let file = std::env::temp_dir().join(format!("polda-auth-{}.json", std::process::id()));
+        fs::write(&file, r#"[{ "token": "t1", "user": "alice" }]"#).unwrap();
+        let auth = Auth::new().with(StaticTokens::load(&file).unwrap());
+        fs::remove_file(&file).unwrap();
+
+        let alice = Some(User { id: String::from("alice"), name: None });
+        let req = TestRequest::default()
+            .insert_header((AUTHORIZATION, "Bearer t1"))
+            .to_http_request();
+        assert_eq!(auth.authenticate(&req), alice);
+        let req = TestRequest::with_uri("/ws?token=t1").to_http_request();
+        assert_eq!(auth.authenticate(&req), alice);
+        let req = TestRequest::with_uri("/ws?token=t2").to_http_request();
+        assert_eq!(auth.authenticate(&req), None);
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(auth.authenticate(&req), None);
+
+        let req = TestRequest::default().to_http_request();
+        assert_eq!(Auth::new().authenticate(&req), Some(User::anonymous()));
+    }
+}
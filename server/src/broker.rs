@@ -1,24 +1,42 @@
 use actix::Addr;
 use actix::Actor;
+use actix::AsyncContext;
 use actix::Context;
 use actix::Message as MessageTrait;
 use actix::Handler;
 use actix::Supervised;
 use actix::SystemService;
+use query::context::ExecutionContext;
 use query::error::PoldaError;
 use std::collections::HashMap;
 
+use crate::acl::SharedAcl;
+use crate::client::Client;
+use crate::client::RpcResponseMsg;
 use crate::document::Document;
+use crate::document::FilesChangedMsg;
+use crate::sources::list_sources_blocking;
+use crate::storage::doc_key;
 
 #[derive(Default)]
 pub struct Broker {
     documents: HashMap<String, Addr<Document>>,
+    /// The ACLs of the open documents.
+    acls: HashMap<String, SharedAcl>,
+    clients: HashMap<String, Addr<Client>>,
+    context: ExecutionContext,
+    /// The user that owns documents saved before they had an ACL.
+    admin: Option<String>
 }
 
 impl Broker {
-    pub fn new() -> Broker {
+    pub fn new(context: ExecutionContext, admin: Option<String>) -> Broker {
         Broker {
-            documents: HashMap::new()
+            documents: HashMap::new(),
+            acls: HashMap::new(),
+            clients: HashMap::new(),
+            context,
+            admin
         }
     }
 }
@@ -27,10 +45,15 @@ impl Actor for Broker {
     type Context = Context<Broker>;
 }
 
+/// Open a document for a user.  Users without a role get
+/// `PoldaError::Forbidden`.  A document that doesn't exist yet is only
+/// created with `create`, and its creator becomes its owner.
 #[derive(MessageTrait)]
 #[rtype(result = "Result<Addr<Document>, PoldaError>")]
 pub struct OpenDocumentMsg {
-    pub path: String
+    pub path: String,
+    pub user_id: String,
+    pub create: bool
 }
 
 impl Handler<OpenDocumentMsg> for Broker {
@@ -41,22 +64,38 @@ impl Handler<OpenDocumentMsg> for Broker {
         msg: OpenDocumentMsg,
         _ctx: &mut Context<Broker>
     ) -> Result<Addr<Document>, PoldaError> {
-        let OpenDocumentMsg { path } = msg;
-        if let Some(doc) = self.documents.get(&path) {
-            Ok(doc.clone())
-        } else {
-            match Document::open(path.clone()) {
-                Ok(doc) => {
-                    let doc = doc.start();
-                    self.documents.insert(path, doc.clone());
-                    Ok(doc)
+        let OpenDocumentMsg { path, user_id, create } = msg;
+        // Different spellings of a path must share one actor.
+        let key = doc_key(self.context.project_dir(), &path)?;
+        let (doc, acl) = match (self.documents.get(&key), self.acls.get(&key)) {
+            (Some(doc), Some(acl)) => (doc.clone(), acl.clone()),
+            _ => {
+                let mut doc = Document::open(key.clone(), self.context.clone())?;
+                let owner = doc
+                    .acl()
+                    .lock()
+                    .unwrap()
+                    .owner_to_claim(&path, doc.is_new(), create, &user_id, self.admin.as_deref())?;
+                if let Some(owner) = owner {
+                    doc.set_owner(owner)?;
                 }
-                Err(e) => Err(e)
+                let acl = doc.acl();
+                let doc = doc.start();
+                self.documents.insert(key.clone(), doc.clone());
+                self.acls.insert(key, acl.clone());
+                (doc, acl)
             }
+        };
+
+        if acl.lock().unwrap().role(&user_id).is_some() {
+            Ok(doc)
+        } else {
+            Err(PoldaError::Forbidden(format!("You don't have access to \"{}\"", path)))
         }
     }
 }
 
+/// Sent by a stopping document with the key it was opened with.
 #[derive(MessageTrait)]
 #[rtype(result = "()")]
 pub struct CloseDocumentMsg {
@@ -72,6 +111,93 @@ impl Handler<CloseDocumentMsg> for Broker {
         _ctx: &mut Context<Broker>
     ) {
         self.documents.remove(&msg.path);
+        self.acls.remove(&msg.path);
+    }
+}
+
+#[derive(MessageTrait)]
+#[rtype(result = "()")]
+pub struct ConnectClientMsg {
+    pub id: String,
+    pub client: Addr<Client>
+}
+
+impl Handler<ConnectClientMsg> for Broker {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: ConnectClientMsg,
+        _ctx: &mut Context<Broker>
+    ) {
+        self.clients.insert(msg.id, msg.client);
+    }
+}
+
+#[derive(MessageTrait)]
+#[rtype(result = "()")]
+pub struct DisconnectClientMsg {
+    pub id: String
+}
+
+impl Handler<DisconnectClientMsg> for Broker {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: DisconnectClientMsg,
+        _ctx: &mut Context<Broker>
+    ) {
+        self.clients.remove(&msg.id);
+    }
+}
+
+/// Files in the project directory have been added or removed.  Every client
+/// gets the new list of sources.
+#[derive(MessageTrait)]
+#[rtype(result = "()")]
+pub struct SourcesChangedMsg;
+
+impl Handler<SourcesChangedMsg> for Broker {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        _msg: SourcesChangedMsg,
+        _ctx: &mut Context<Broker>
+    ) {
+        let clients: Vec<Addr<Client>> = self.clients.values().cloned().collect();
+        let context = self.context.clone();
+        actix::spawn(async move {
+            match list_sources_blocking(context, String::new(), false).await {
+                Ok(sources) => {
+                    for client in clients.iter() {
+                        let msg = RpcResponseMsg::Sources {
+                            id: None,
+                            dir: String::new(),
+                            sources: sources.clone()
+                        };
+                        client.do_send(msg);
+                    }
+                }
+                Err(e) => log::error!("failed to list sources: {}", e)
+            }
+        });
+    }
+}
+
+impl Handler<FilesChangedMsg> for Broker {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: FilesChangedMsg,
+        ctx: &mut Context<Broker>
+    ) {
+        ctx.notify(SourcesChangedMsg);
+        for doc in self.documents.values() {
+            doc.do_send(msg.clone());
+        }
     }
 }
 
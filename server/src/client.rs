@@ -14,56 +14,94 @@ use actix_web_actors::ws::ProtocolError;
 use actix_web_actors::ws::WebsocketContext;
 use once_cell::sync::Lazy;
 use query::DataFrame;
+use query::column::Column;
+use query::context::ExecutionContext;
+use query::doc::Diagnostic;
 use query::doc::Doc;
 use query::doc::Operation;
+use query::doc::Position;
+use query::doc::Sorter;
+use query::doc::View;
+use query::error::PoldaError;
+use query::query::Schema;
+use query::utils::collect_ipc_stream;
 use rand::distributions::Alphanumeric;
 use rand::prelude::Distribution;
 use rand::thread_rng;
 use serde::Deserialize;
 use serde::Serialize;
+use std::collections::BTreeMap;
+use std::collections::HashMap;
 use std::collections::HashSet;
-use std::path::Path;
 use std::sync::Mutex;
 use std::time::Duration;
 use std::time::Instant;
 
+use crate::acl::Role;
+use crate::auth::User;
 use crate::broker::Broker;
+use crate::broker::ConnectClientMsg;
+use crate::broker::DisconnectClientMsg;
+use crate::broker::SourcesChangedMsg;
 use crate::broker::OpenDocumentMsg;
 use crate::document::Document;
+use crate::document::GetAclMsg;
 use crate::document::GetDocMsg;
+use crate::document::GetOperationsMsg;
+use crate::document::GetSchemasMsg;
 use crate::document::QueryMsg;
+use crate::document::MaterializeMsg;
 use crate::document::ReadFileMsg;
+use crate::document::SetAwarenessMsg;
+use crate::document::SetRoleMsg;
 use crate::document::SubscribeMsg;
 use crate::document::UnsubscribeMsg;
 use crate::document::UpdateDocMsg;
 use crate::executor::CancelJobMsg;
 use crate::executor::Executor;
+use crate::sources::Source;
+use crate::sources::list_sources_blocking;
 
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
 const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Limits of an awareness state, which is sent to every collaborator.
+const MAX_AWARENESS_NAME: usize = 64;
+const MAX_AWARENESS_COLOR: usize = 32;
+const MAX_AWARENESS_SELECTED: usize = 256;
 static CLIENT_IDS: Lazy<Mutex<HashSet<String>>> = Lazy::new(|| {
     Mutex::new(HashSet::new())
 });
 
 pub struct Client {
     id: String,
+    user: User,
     document: Option<Addr<Document>>,
+    context: ExecutionContext,
+    result_format: ResultFormat,
     hb: Instant
 }
 
 impl Client {
-    pub fn new() -> Client {
+    pub fn new(context: ExecutionContext, user: User) -> Client {
         Client {
             id: new_client_id(),
+            user,
             document: None,
+            context,
+            result_format: ResultFormat::default(),
             hb: Instant::now()
         }
     }
 
     fn open_doc(&mut self, id: usize, path: String, ctx: &mut WebsocketContext<Client>) {
         self.unsubscribe();
-        let msg = OpenDocumentMsg { path };
+        let msg = OpenDocumentMsg {
+            path,
+            user_id: self.user.id.clone(),
+            create: true
+        };
         let client_id = self.id.clone();
+        let user_id = self.user.id.clone();
         <Broker as SystemService>::from_registry()
             .send(msg)
             .into_actor(self)
@@ -71,7 +109,8 @@ impl Client {
                 match doc {
                     Ok(Ok(doc)) => {
                         let msg = SubscribeMsg {
-                            id: client_id,
+                            id: client_id.clone(),
+                            user_id,
                             client: ctx.address()
                         };
                         doc
@@ -82,6 +121,7 @@ impl Client {
                                     // Get the latest doc.
                                     let msg = GetDocMsg {
                                         client: ctx.address(),
+                                        client_id,
                                         req_id: id
                                     };
                                     doc.do_send(msg);
@@ -99,9 +139,13 @@ impl Client {
                             .wait(ctx)
                     }
                     Ok(Err(e)) => {
+                        let code = match e {
+                            PoldaError::Forbidden(_) => RpcErrorCode::Forbidden,
+                            _ => RpcErrorCode::InternalError
+                        };
                         let msg = RpcResponseMsg::Error {
                             id: Some(id),
-                            code: RpcErrorCode::InternalError,
+                            code,
                             msg: e.to_string()
                         };
                         ctx.address().do_send(msg);
@@ -132,6 +176,62 @@ impl Client {
         }
     }
 
+    /// Send a result as a binary message: the length of the JSON header as a
+    /// big-endian u32, the header, and the frame as an Arrow IPC stream.
+    fn binary_response(
+        id: usize,
+        header: &BinaryHeader,
+        data: &mut DataFrame,
+        ctx: &mut WebsocketContext<Client>
+    ) {
+        let res = serde_json::to_vec(header)
+            .map_err(|e| PoldaError::InternalError(e.to_string()))
+            .and_then(|header| Ok((header, collect_ipc_stream(data)?)));
+
+        match res {
+            Ok((header, stream)) => {
+                let mut msg = Vec::with_capacity(4 + header.len() + stream.len());
+                msg.extend_from_slice(&(header.len() as u32).to_be_bytes());
+                msg.extend(header);
+                msg.extend(stream);
+                ctx.binary(msg);
+            }
+            Err(e) => {
+                let res = RpcResponseMsg::Error {
+                    id: Some(id),
+                    code: RpcErrorCode::InternalError,
+                    msg: e.to_string()
+                };
+                Self::response(&res, ctx);
+            }
+        }
+    }
+
+    /// List the sources without blocking the client.
+    fn list_sources(
+        &self,
+        id: Option<usize>,
+        dir: String,
+        refresh: bool,
+        ctx: &mut WebsocketContext<Client>
+    ) {
+        let context = self.context.clone();
+        let addr = ctx.address();
+        actix::spawn(async move {
+            let msg = match list_sources_blocking(context, dir.clone(), refresh).await {
+                Ok(sources) => RpcResponseMsg::Sources { id, dir, sources },
+                Err(e) => {
+                    RpcResponseMsg::Error {
+                        id,
+                        code: RpcErrorCode::InvalidParams,
+                        msg: e.to_string()
+                    }
+                }
+            };
+            addr.do_send(msg);
+        });
+    }
+
     fn unsubscribe(&mut self) {
         if let Some(doc) = self.document.take() {
             let msg = UnsubscribeMsg {
@@ -154,17 +254,29 @@ impl Actor for Client {
             }
             ctx.ping(b"");
         });
-        let msg = RpcResponseMsg::ClientId { client_id: self.id.clone() };
+        let msg = RpcResponseMsg::ClientId {
+            client_id: self.id.clone(),
+            user: self.user.clone()
+        };
         ctx.address().do_send(msg);
 
-        let dir = Path::new(".");
-        let sources = get_sources(dir);
-        let msg = RpcResponseMsg::Sources { sources };
-        ctx.address().do_send(msg);
+        let msg = ConnectClientMsg {
+            id: self.id.clone(),
+            client: ctx.address()
+        };
+        <Broker as SystemService>::from_registry()
+            .do_send(msg);
+
+        self.list_sources(None, String::new(), false, ctx);
     }
 
     fn stopped(&mut self, _ctx: &mut WebsocketContext<Client>) {
         self.unsubscribe();
+        let msg = DisconnectClientMsg {
+            id: self.id.clone()
+        };
+        <Broker as SystemService>::from_registry()
+            .do_send(msg);
         let mut ids = CLIENT_IDS.lock().unwrap();
         ids.remove(&self.id);
     }
@@ -206,6 +318,7 @@ impl StreamHandler<Result<Message, ProtocolError>> for Client {
                                 let msg = UpdateDocMsg {
                                     req_id: id,
                                     client_id: self.id.clone(),
+                                    author: self.user.id.clone(),
                                     version,
                                     operations
                                 };
@@ -223,6 +336,7 @@ impl StreamHandler<Result<Message, ProtocolError>> for Client {
                             if let Some(addr) = &self.document {
                                 let msg = GetDocMsg {
                                     client: ctx.address(),
+                                    client_id: self.id.clone(),
                                     req_id: id
                                 };
                                 addr.do_send(msg);
@@ -235,13 +349,49 @@ impl StreamHandler<Result<Message, ProtocolError>> for Client {
                                 ctx.address().do_send(msg);
                             }
                         }
-                        Query { id, node_id } => {
+                        GetOperations { id, since_version } => {
+                            if let Some(addr) = &self.document {
+                                let msg = GetOperationsMsg {
+                                    client: ctx.address(),
+                                    client_id: self.id.clone(),
+                                    req_id: id,
+                                    since_version
+                                };
+                                addr.do_send(msg);
+                            } else {
+                                let msg = RpcResponseMsg::Error {
+                                    id: Some(id),
+                                    code: RpcErrorCode::InvalidRequest,
+                                    msg: String::from("Open doc before requesting operations!")
+                                };
+                                ctx.address().do_send(msg);
+                            }
+                        }
+                        GetSchemas { id } => {
+                            if let Some(addr) = &self.document {
+                                let msg = GetSchemasMsg {
+                                    client: ctx.address(),
+                                    client_id: self.id.clone(),
+                                    req_id: id
+                                };
+                                addr.do_send(msg);
+                            } else {
+                                let msg = RpcResponseMsg::Error {
+                                    id: Some(id),
+                                    code: RpcErrorCode::InvalidRequest,
+                                    msg: String::from("Open doc before requesting schemas!")
+                                };
+                                ctx.address().do_send(msg);
+                            }
+                        }
+                        Query { id, node_id, offset, limit, sorters } => {
                             if let Some(addr) = &self.document {
                                 let msg = QueryMsg {
                                     client: ctx.address(),
                                     client_id: self.id.clone(),
                                     req_id: id,
-                                    node_id
+                                    node_id,
+                                    view: View { offset, limit, sorters }
                                 };
                                 addr.do_send(msg);
                             } else {
@@ -253,6 +403,24 @@ impl StreamHandler<Result<Message, ProtocolError>> for Client {
                                 ctx.address().do_send(msg);
                             }
                         }
+                        Materialize { id, node_id } => {
+                            if let Some(addr) = &self.document {
+                                let msg = MaterializeMsg {
+                                    client: ctx.address(),
+                                    client_id: self.id.clone(),
+                                    req_id: id,
+                                    node_id
+                                };
+                                addr.do_send(msg);
+                            } else {
+                                let msg = RpcResponseMsg::Error {
+                                    id: Some(id),
+                                    code: RpcErrorCode::InvalidRequest,
+                                    msg: String::from("Open doc before materializing!")
+                                };
+                                ctx.address().do_send(msg);
+                            }
+                        }
                         ReadFile { id, filename } => {
                             if let Some(addr) = &self.document {
                                 let msg = ReadFileMsg {
@@ -273,7 +441,7 @@ impl StreamHandler<Result<Message, ProtocolError>> for Client {
                         }
                         CancelJob { id } => {
                             let msg = CancelJobMsg {
-                                client: ctx.address(),
+                                client: Some(ctx.address()),
                                 client_id: self.id.clone(),
                                 job_id: id,
                             };
@@ -283,6 +451,59 @@ impl StreamHandler<Result<Message, ProtocolError>> for Client {
                         CloseDoc { id } => {
                             self.close_doc(id, ctx);
                         }
+                        GetAcl { id } => {
+                            if let Some(addr) = &self.document {
+                                let msg = GetAclMsg {
+                                    client: ctx.address(),
+                                    client_id: self.id.clone(),
+                                    req_id: id
+                                };
+                                addr.do_send(msg);
+                            } else {
+                                let msg = RpcResponseMsg::Error {
+                                    id: Some(id),
+                                    code: RpcErrorCode::InvalidRequest,
+                                    msg: String::from("Open doc before requesting the ACL!")
+                                };
+                                ctx.address().do_send(msg);
+                            }
+                        }
+                        SetAwareness { state } => {
+                            if let Some(addr) = &self.document {
+                                let msg = SetAwarenessMsg {
+                                    client_id: self.id.clone(),
+                                    state
+                                };
+                                addr.do_send(msg);
+                            }
+                        }
+                        SetRole { id, user_id, role } => {
+                            if let Some(addr) = &self.document {
+                                let msg = SetRoleMsg {
+                                    client: ctx.address(),
+                                    client_id: self.id.clone(),
+                                    req_id: id,
+                                    user_id,
+                                    role
+                                };
+                                addr.do_send(msg);
+                            } else {
+                                let msg = RpcResponseMsg::Error {
+                                    id: Some(id),
+                                    code: RpcErrorCode::InvalidRequest,
+                                    msg: String::from("Open doc before sharing it!")
+                                };
+                                ctx.address().do_send(msg);
+                            }
+                        }
+                        ListSources { id, dir, refresh } => {
+                            self.list_sources(Some(id), dir, refresh, ctx);
+                        }
+                        SetResultFormat { id, format } => {
+                            self.result_format = format;
+                            let res = RpcResponseMsg::ResultFormat { id, format };
+                            Self::response(&res, ctx);
+                        }
                     }
                 } else {
                     let res = RpcResponseMsg::Error {
@@ -324,7 +545,27 @@ pub enum RpcRequest {
     GetDoc {
         id: usize
     },
+    GetOperations {
+        id: usize,
+        since_version: usize
+    },
+    GetSchemas {
+        id: usize
+    },
+    /// Request a page of the result of a node.  The sorters only apply to
+    /// this view of the result and don't change the doc.
     Query {
+        id: usize,
+        node_id: String,
+        #[serde(default)]
+        offset: usize,
+        #[serde(default)]
+        limit: Option<usize>,
+        #[serde(default)]
+        sorters: Vec<Sorter>
+    },
+    /// Run a sink node and write its file.
+    Materialize {
         id: usize,
         node_id: String
     },
@@ -338,6 +579,61 @@ pub enum RpcRequest {
     CloseDoc {
         id: usize
     },
+    GetAcl {
+        id: usize
+    },
+    /// Share what the user is doing with the other clients of the open doc.
+    /// There's no response.  The state is kept until the client closes the
+    /// doc or stops answering heartbeats.
+    SetAwareness {
+        state: Awareness
+    },
+    /// Share the open doc with a user.  No role revokes the user's access.
+    SetRole {
+        id: usize,
+        user_id: String,
+        #[serde(default)]
+        role: Option<Role>
+    },
+    /// List the source files in `dir` and its subdirectories.  `refresh`
+    /// peeks the schemas of unchanged files again.
+    ListSources {
+        id: usize,
+        #[serde(default)]
+        dir: String,
+        #[serde(default)]
+        refresh: bool
+    },
+    /// Choose how query results and file data are sent.
+    SetResultFormat {
+        id: usize,
+        format: ResultFormat
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResultFormat {
+    /// Results are serialized in `RpcResponseMsg` text messages.
+    #[default]
+    Json,
+    /// Results are sent as Arrow IPC streams in binary messages.
+    ArrowIpc
+}
+
+/// Correlates a binary message with its request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum BinaryHeader {
+    QueryResult {
+        id: usize,
+        offset: usize,
+        total_rows: usize
+    },
+    FileData {
+        id: usize
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -348,6 +644,11 @@ pub enum RpcErrorCode {
     MethodNotFound,
     InvalidParams,
     InternalError,
+    /// The requested operations have been compacted.  The client must
+    /// refetch the whole doc.
+    SnapshotRequired,
+    /// The user's role doesn't allow the request.
+    Forbidden,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, MessageTrait)]
@@ -356,37 +657,91 @@ pub enum RpcErrorCode {
 #[serde(rename_all = "snake_case")]
 pub enum RpcResponseMsg {
     ClientId {
-        client_id: String
+        client_id: String,
+        user: User
     },
+    /// Sent on request, on connect and to every client after files have
+    /// been added or removed.
     Sources {
-        sources: Vec<String>
+        id: Option<usize>,
+        dir: String,
+        sources: Vec<Source>
     },
     Doc {
         id: usize,
         version: usize,
+        /// The role of the user in this doc.
+        role: Role,
         doc: Doc
     },
+    /// The awareness state of another client of the open doc.  No state
+    /// means the client has left.
+    Awareness {
+        client_id: String,
+        user_id: String,
+        state: Option<Awareness>
+    },
+    /// Sent on request and to every subscribed client after the ACL changed.
+    Acl {
+        id: Option<usize>,
+        roles: BTreeMap<String, Role>
+    },
     DocClosed {
         id: usize
     },
+    Operations {
+        id: usize,
+        version: usize,
+        operations: Vec<Operation>,
+        /// The user who made each operation, if known.
+        authors: Vec<Option<String>>
+    },
     UpdateDoc {
         /// The client that make the update gets a response with an id, others
         /// don't.
         id: Option<usize>,
         version: usize,
+        author: String,
         operations: Vec<Operation>
     },
+    /// Sent on request and to every subscriber after the graph changes.
+    Schemas {
+        id: Option<usize>,
+        version: usize,
+        schemas: HashMap<String, NodeSchema>
+    },
+    /// Pushed with the doc and after the graph changes.
+    Diagnostics {
+        version: usize,
+        diagnostics: Vec<Diagnostic>
+    },
+    /// Pushed after source files changed on disk.  The schemas of these nodes
+    /// differ from the ones before the change.
+    SchemaDrift {
+        version: usize,
+        node_ids: Vec<String>
+    },
     QueryResult {
         id: usize,
-        data: DataFrame
+        data: DataFrame,
+        offset: usize,
+        total_rows: usize
     },
     FileData {
         id: usize,
         data: DataFrame
     },
+    Materialized {
+        id: usize,
+        rows: usize
+    },
     JobCanceled {
         id: usize
     },
+    ResultFormat {
+        id: usize,
+        format: ResultFormat
+    },
     Error {
         id: Option<usize>,
         code: RpcErrorCode,
@@ -394,6 +749,71 @@ pub enum RpcResponseMsg {
     }
 }
 
+/// What a collaborator is doing in the open doc.  It isn't part of the doc
+/// and isn't persisted.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Awareness {
+    #[serde(default)]
+    pub name: String,
+    /// A CSS color.
+    #[serde(default)]
+    pub color: String,
+    #[serde(default)]
+    pub selected: Vec<String>,
+    #[serde(default)]
+    pub viewport: Option<Viewport>,
+    /// In canvas coordinates.
+    #[serde(default)]
+    pub cursor: Option<Position>
+}
+
+impl Awareness {
+    /// Truncate the strings and keep only distinct nodes that exist in the
+    /// doc.
+    pub fn bound<F: Fn(&str) -> bool>(&mut self, is_node: F) {
+        truncate_chars(&mut self.name, MAX_AWARENESS_NAME);
+        truncate_chars(&mut self.color, MAX_AWARENESS_COLOR);
+        let mut seen = HashSet::new();
+        self.selected.retain(|id| is_node(id) && seen.insert(id.clone()));
+        self.selected.truncate(MAX_AWARENESS_SELECTED);
+    }
+}
+
+fn truncate_chars(s: &mut String, max: usize) {
+    if let Some((i, _)) = s.char_indices().nth(max) {
+        s.truncate(i);
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Viewport {
+    pub x: f64,
+    pub y: f64,
+    pub zoom: f64
+}
+
+/// The output columns of a node, or the reason the node is invalid.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status")]
+#[serde(rename_all = "snake_case")]
+pub enum NodeSchema {
+    Valid {
+        columns: Vec<Column>
+    },
+    Invalid {
+        msg: String
+    }
+}
+
+impl From<Result<Schema, PoldaError>> for NodeSchema {
+    fn from(schema: Result<Schema, PoldaError>) -> NodeSchema {
+        match schema {
+            Ok(schema) => NodeSchema::Valid { columns: schema.columns().to_vec() },
+            Err(e) => NodeSchema::Invalid { msg: e.to_string() }
+        }
+    }
+}
+
 impl Handler<RpcResponseMsg> for Client {
     type Result = ();
 
@@ -402,8 +822,26 @@ impl Handler<RpcResponseMsg> for Client {
         msg: RpcResponseMsg,
         ctx: &mut WebsocketContext<Client>
     ) {
-        if let Ok(msg) = serde_json::to_string(&msg) {
-            ctx.text(msg);
+        match msg {
+            RpcResponseMsg::QueryResult { id, mut data, offset, total_rows }
+                if self.result_format == ResultFormat::ArrowIpc =>
+            {
+                let header = BinaryHeader::QueryResult { id, offset, total_rows };
+                Self::binary_response(id, &header, &mut data, ctx);
+            }
+            RpcResponseMsg::FileData { id, mut data }
+                if self.result_format == ResultFormat::ArrowIpc =>
+            {
+                let header = BinaryHeader::FileData { id };
+                Self::binary_response(id, &header, &mut data, ctx);
+            }
+            RpcResponseMsg::Materialized { id, rows } => {
+                Self::response(&RpcResponseMsg::Materialized { id, rows }, ctx);
+                // The written file is a new source.
+                <Broker as SystemService>::from_registry()
+                    .do_send(SourcesChangedMsg);
+            }
+            msg => Self::response(&msg, ctx)
         }
     }
 }
@@ -432,26 +870,26 @@ fn random_string(len: usize) -> String {
         .collect()
 }
 
-fn get_sources<T: AsRef<Path>>(dir: T) -> Vec<String> {
-    let mut sources = vec![];
-    if let Ok(entries) = dir.as_ref().read_dir() {
-        entries.for_each(|entry_res| {
-            if let Ok(entry) = entry_res {
-                if let Ok(ftype) = entry.file_type(){
-                    if ftype.is_file() {
-                        let path = entry.path();
-                        if let Some(filename) = path.file_name() {
-                            let filename = filename
-                                .to_string_lossy()
-                                .to_string();
-                            if filename.ends_with(".csv") {
-                                sources.push(filename);
-                            }
-                        }
-                    }
-                }
-            }
-        });
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bound_awareness() {
+        let mut state = Awareness {
+            name: "é".repeat(100),
+            color: String::from("#ff0000"),
+            selected: vec![String::from("a"), String::from("x"), String::from("a"), String::from("b")],
+            viewport: None,
+            cursor: None
+        };
+        state.bound(|id| id != "x");
+        assert_eq!(state.name, "é".repeat(MAX_AWARENESS_NAME));
+        assert_eq!(state.color, "#ff0000");
+        assert_eq!(state.selected, vec![String::from("a"), String::from("b")]);
+
+        state.selected = (0..1000).map(|i| i.to_string()).collect();
+        state.bound(|_| true);
+        assert_eq!(state.selected.len(), MAX_AWARENESS_SELECTED);
     }
-    sources
 }
@@ -3,69 +3,441 @@ use actix::Actor;
 use actix::ActorContext;
 use actix::AsyncContext;
 use actix::Context;
+use actix::ContextFutureSpawner;
+use actix::fut::future::ActorFutureExt;
 use actix::Message as MessageTrait;
 use actix::Handler;
 use actix::Running;
 use actix::SystemService;
-use query::doc::Aggregate;
-use query::doc::AggregateComputation;
+use actix::WrapFuture;
+use query::context::ExecutionContext;
+use query::doc::Diagnostic;
 use query::doc::Doc;
-use query::doc::InputName;
-use query::doc::Node;
 use query::doc::Operation;
-use query::doc::Position;
-use query::doc::SortDirection;
-use query::doc::Sorter;
 use query::doc::transform_batch;
 use query::doc::validate_sequence;
+use query::doc::View;
 use query::error::PoldaError;
+use query::export::ExportFormat;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::mem;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::sync::Mutex;
 use std::time::Duration;
 use std::time::Instant;
+use tokio::task;
 
+use crate::acl::Acl;
+use crate::acl::Role;
+use crate::acl::SharedAcl;
 use crate::broker::Broker;
 use crate::broker::CloseDocumentMsg;
+use crate::client::Awareness;
 use crate::client::Client;
+use crate::client::NodeSchema;
 use crate::client::RpcErrorCode;
 use crate::client::RpcResponseMsg;
 use crate::executor::Executor;
+use crate::executor::ExportSender;
+use crate::executor::InvalidateCacheMsg;
 use crate::executor::Job;
 use crate::executor::JobMsg;
 use crate::executor::JobKind;
+use crate::storage::doc_file_path;
+use crate::storage::load_doc;
+use crate::storage::log_file_path;
+use crate::storage::LogEntry;
+use crate::storage::OperationLog;
+use crate::storage::save_doc;
 
 const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(10);
 const TIMEOUT: Duration = Duration::from_secs(60);
+/// Write a new snapshot after this many operations.
+const SNAPSHOT_INTERVAL: usize = 100;
+/// Compact the operations once this many of them are no longer needed by any
+/// subscribed client.
+const COMPACTION_THRESHOLD: usize = 100;
+/// Compact regardless of the subscribed clients when the history grows beyond
+/// this limit.  Clients that fall behind have to refetch the doc.
+const MAX_OPERATIONS: usize = 10_000;
 
 pub struct Document {
+    /// The key of the doc in the broker, see `doc_key`.
     path: String,
+    file: PathBuf,
+    log: OperationLog,
     doc: Doc,
     operations: Vec<Operation>,
+    /// The user who made each operation.
+    authors: Vec<Option<String>>,
     deleted_ops: usize,
+    snapshot_version: usize,
     clients: HashMap<String, Addr<Client>>,
+    /// The id of the user behind each subscribed client.
+    users: HashMap<String, String>,
+    acl: SharedAcl,
+    /// The awareness states of the subscribed clients.  A state lives as
+    /// long as its client is subscribed.
+    awareness: HashMap<String, Awareness>,
+    /// The latest version each client has acknowledged.  Operations after
+    /// this version may still be needed to rebase the client's updates.
+    client_versions: HashMap<String, usize>,
+    context: ExecutionContext,
+    /// The latest analysis.  It lags behind the doc while a new one is
+    /// computed.
+    analysis: Option<Analysis>,
+    /// Whether an analysis is being computed.
+    analyzing: bool,
+    /// Whether the doc has changed since the last analysis started.
+    edited: bool,
+    /// Source files that have changed since the last analysis started.
+    changed_paths: HashSet<PathBuf>,
+    /// Schema requests waiting for the analysis of the current version.
+    schema_requests: Vec<(Addr<Client>, usize)>,
     hb: Instant
 }
 
+struct Analysis {
+    version: usize,
+    schemas: HashMap<String, NodeSchema>,
+    diagnostics: Vec<Diagnostic>,
+    /// Cache keys of the node results, see `Doc::hashes`.
+    hashes: HashMap<String, u64>
+}
+
 impl Document {
-    pub fn open(path: String) -> Result<Document, PoldaError> {
+    /// Open a document from the project directory.  The latest snapshot is
+    /// loaded and the operations logged after it are replayed.  A document
+    /// that doesn't exist yet starts empty and is created on the first
+    /// update.
+    pub fn open(path: String, context: ExecutionContext) -> Result<Document, PoldaError> {
+        let file = doc_file_path(context.project_dir(), &path)?;
+        let log = OperationLog::new(log_file_path(&file));
+        let (mut doc, snapshot_version, acl) = load_doc(&file)?
+            .unwrap_or_else(|| (Doc::new(), 0, Acl::default()));
+
+        let entries = log.read()?;
+        let deleted_ops = entries
+            .first()
+            .map(|entry| entry.version)
+            .unwrap_or(snapshot_version);
+        if deleted_ops > snapshot_version {
+            return Err(PoldaError::DocError(format!("Operation log of \"{}\" doesn't start at the snapshot", path)));
+        }
+
+        let mut operations = vec![];
+        let mut authors = vec![];
+        for entry in entries.into_iter() {
+            let LogEntry { version, author, operations: ops } = entry;
+            if version != deleted_ops + operations.len() {
+                return Err(PoldaError::DocError(format!("Operation log of \"{}\" has a gap at version {}", path, version)));
+            }
+            // Replay the operations that aren't part of the snapshot yet.
+            let skip = snapshot_version.saturating_sub(version);
+            let replay = ops.iter().skip(skip).cloned().collect::<Vec<_>>();
+            if !replay.is_empty() {
+                doc.execute_operations(replay)?;
+            }
+            authors.extend(ops.iter().map(|_| author.clone()));
+            operations.extend(ops);
+        }
+
+        if deleted_ops + operations.len() < snapshot_version {
+            return Err(PoldaError::DocError(format!("Operation log of \"{}\" is older than the snapshot", path)));
+        }
+
         Ok(Document {
             path,
-            doc: demo_doc(),
-            operations: vec![],
-            deleted_ops: 0,
+            file,
+            log,
+            doc,
+            operations,
+            authors,
+            deleted_ops,
+            snapshot_version,
             clients: HashMap::new(),
+            users: HashMap::new(),
+            acl: Arc::new(Mutex::new(acl)),
+            awareness: HashMap::new(),
+            client_versions: HashMap::new(),
+            context,
+            analysis: None,
+            analyzing: false,
+            edited: true,
+            changed_paths: HashSet::new(),
+            schema_requests: vec![],
             hb: Instant::now()
         })
     }
+
+    /// Whether the doc has never been saved.
+    pub fn is_new(&self) -> bool {
+        self.version() == 0 && !self.file.exists()
+    }
+
+    /// Make a user an owner of the doc and save it right away, so nobody
+    /// else can claim it.
+    pub fn set_owner(&mut self, user_id: String) -> Result<(), PoldaError> {
+        self.acl.lock().unwrap().set_role(user_id, Some(Role::Owner))?;
+        self.snapshot()
+    }
+
+    pub fn acl(&self) -> SharedAcl {
+        self.acl.clone()
+    }
+
+    /// Whether the user behind a subscribed client has at least `required`.
+    fn allows(&self, client_id: &str, required: Role) -> bool {
+        match self.users.get(client_id) {
+            Some(user_id) => self.acl.lock().unwrap().allows(user_id, required),
+            None => false
+        }
+    }
+
+    /// Send the awareness state of a client to the other clients.
+    fn broadcast_awareness(&self, client_id: &str, state: Option<Awareness>) {
+        let user_id = match self.users.get(client_id) {
+            Some(user_id) => user_id.clone(),
+            None => return
+        };
+        self.clients
+            .iter()
+            .filter(|(id, _)| *id != client_id)
+            .for_each(|(_, client)| {
+                let msg = RpcResponseMsg::Awareness {
+                    client_id: client_id.to_string(),
+                    user_id: user_id.clone(),
+                    state: state.clone()
+                };
+                client.do_send(msg);
+            });
+    }
+
+    /// Tell the other clients that a client has left.  Call this before the
+    /// client is removed from `users`.
+    fn drop_awareness(&mut self, client_id: &str) {
+        if self.awareness.remove(client_id).is_some() {
+            self.broadcast_awareness(client_id, None);
+        }
+    }
+
+    fn unsubscribe(&mut self, client_id: &str) {
+        self.drop_awareness(client_id);
+        self.clients.remove(client_id);
+        self.users.remove(client_id);
+        self.client_versions.remove(client_id);
+        if let Err(e) = self.compact() {
+            log::error!("failed to compact {}: {}", self.path, e);
+        }
+    }
+
+    fn version(&self) -> usize {
+        self.deleted_ops + self.operations.len()
+    }
+
+    /// The analysis of the current version, if it's ready.
+    fn current_analysis(&self) -> Option<&Analysis> {
+        self.analysis
+            .as_ref()
+            .filter(|analysis| analysis.version == self.version())
+    }
+
+    /// Analyze the doc on a blocking thread, because schema inference reads
+    /// the source files.  Changes made in the meantime are picked up by the
+    /// next analysis once this one is done.
+    fn request_analysis(&mut self, ctx: &mut Context<Document>) {
+        if self.analyzing || (!self.edited && self.changed_paths.is_empty()) {
+            return;
+        }
+        self.analyzing = true;
+        let edited = mem::take(&mut self.edited);
+        let paths = mem::take(&mut self.changed_paths);
+        let doc = self.doc.clone();
+        let context = self.context.clone();
+        let version = self.version();
+        task::spawn_blocking(move || {
+            let sources = doc.sources_reading(&paths, &context);
+            let stale = doc.dependents(&sources);
+            if !edited && stale.is_empty() {
+                return None;
+            }
+            Some((analyze(&doc, &context, version), stale))
+        })
+            .into_actor(self)
+            .map(|res, act, ctx| {
+                act.analyzing = false;
+                match res {
+                    Ok(Some((analysis, stale))) => act.finish_analysis(analysis, stale),
+                    Ok(None) => (),
+                    Err(e) => log::error!("failed to analyze {}: {}", act.path, e)
+                }
+                act.request_analysis(ctx);
+            })
+            .spawn(ctx);
+    }
+
+    /// Drop the cached results the new analysis has no key for, tell the
+    /// clients about nodes whose schema changed with their source files, and
+    /// push the new analysis if it's still current.
+    fn finish_analysis(&mut self, analysis: Analysis, stale: HashSet<String>) {
+        if let Some(old_analysis) = &self.analysis {
+            let new_keys: HashSet<u64> = analysis.hashes.values().copied().collect();
+            let keys: Vec<u64> = old_analysis.hashes
+                .values()
+                .filter(|key| !new_keys.contains(key))
+                .copied()
+                .collect();
+            if !keys.is_empty() {
+                <Executor as SystemService>::from_registry()
+                    .do_send(InvalidateCacheMsg { keys });
+            }
+
+            let mut node_ids: Vec<String> = stale
+                .into_iter()
+                .filter(|id| old_analysis.schemas.get(id) != analysis.schemas.get(id))
+                .collect();
+            if !node_ids.is_empty() {
+                node_ids.sort();
+                self.clients
+                    .values()
+                    .for_each(|client| {
+                        let msg = RpcResponseMsg::SchemaDrift {
+                            version: analysis.version,
+                            node_ids: node_ids.clone()
+                        };
+                        client.do_send(msg);
+                    });
+            }
+        }
+        let current = analysis.version == self.version();
+        self.analysis = Some(analysis);
+        if !current {
+            return;
+        }
+
+        let requests = mem::take(&mut self.schema_requests);
+        let Analysis { version, schemas, diagnostics, hashes: _ } = match &self.analysis {
+            Some(analysis) => analysis,
+            None => return
+        };
+        self.clients
+            .values()
+            .for_each(|client| {
+                let msg = RpcResponseMsg::Schemas {
+                    id: None,
+                    version: *version,
+                    schemas: schemas.clone()
+                };
+                client.do_send(msg);
+                let msg = RpcResponseMsg::Diagnostics {
+                    version: *version,
+                    diagnostics: diagnostics.clone()
+                };
+                client.do_send(msg);
+            });
+        for (client, req_id) in requests.into_iter() {
+            let msg = RpcResponseMsg::Schemas {
+                id: Some(req_id),
+                version: *version,
+                schemas: schemas.clone()
+            };
+            client.do_send(msg);
+        }
+    }
+
+    fn snapshot(&mut self) -> Result<(), PoldaError> {
+        let version = self.version();
+        let acl = self.acl.lock().unwrap().clone();
+        save_doc(&self.file, &self.doc, version, &acl)?;
+        self.snapshot_version = version;
+        Ok(())
+    }
+
+    /// Drop the operations every subscribed client has moved past and fold
+    /// them into a snapshot.
+    fn compact(&mut self) -> Result<(), PoldaError> {
+        let version = self.version();
+        let mut until = self.client_versions
+            .values()
+            .copied()
+            .min()
+            .unwrap_or(version)
+            .max(self.deleted_ops);
+        if self.operations.len() > MAX_OPERATIONS {
+            until = until.max(version - MAX_OPERATIONS);
+        }
+        if until - self.deleted_ops < COMPACTION_THRESHOLD {
+            return Ok(());
+        }
+
+        // The snapshot must cover the dropped operations before the log
+        // forgets them.
+        self.snapshot()?;
+        self.operations.drain(..until - self.deleted_ops);
+        self.authors.drain(..until - self.deleted_ops);
+        self.deleted_ops = until;
+
+        // One entry per run of operations by the same author.
+        let mut entries: Vec<LogEntry> = vec![];
+        for (i, (op, author)) in self.operations.iter().zip(self.authors.iter()).enumerate() {
+            match entries.last_mut() {
+                Some(entry) if &entry.author == author => entry.operations.push(op.clone()),
+                _ => entries.push(LogEntry {
+                    version: self.deleted_ops + i,
+                    author: author.clone(),
+                    operations: vec![op.clone()]
+                })
+            }
+        }
+        self.log.rewrite(&entries)
+    }
+
+    /// Persist the ACL.  A doc that hasn't been saved yet gets its ACL with
+    /// the first snapshot.
+    fn save_acl(&mut self) {
+        if let Err(e) = self.snapshot() {
+            log::error!("failed to save the ACL of {}: {}", self.path, e);
+        }
+    }
+
+    /// Persist a batch of operations that has just been applied to the doc.
+    fn persist(&mut self, version: usize, author: String, operations: Vec<Operation>) {
+        let entry = LogEntry { version, author: Some(author), operations };
+        if let Err(e) = self.log.append(&entry) {
+            log::error!("failed to append to the operation log of {}: {}", self.path, e);
+        }
+        if self.version() - self.snapshot_version >= SNAPSHOT_INTERVAL {
+            if let Err(e) = self.snapshot() {
+                log::error!("failed to save a snapshot of {}: {}", self.path, e);
+            }
+        }
+        if let Err(e) = self.compact() {
+            log::error!("failed to compact {}: {}", self.path, e);
+        }
+    }
 }
 
 impl Actor for Document {
     type Context = Context<Document>;
 
     fn started(&mut self, ctx: &mut Context<Document>) {
+        self.request_analysis(ctx);
+
         // Check heart beat.
         AsyncContext::run_interval(ctx, HEARTBEAT_INTERVAL, |act, ctx| {
+            // Clients unsubscribe when they stop, this catches those that
+            // couldn't.
+            let gone: Vec<String> = act.clients
+                .iter()
+                .filter(|(_, client)| !client.connected())
+                .map(|(id, _)| id.clone())
+                .collect();
+            for id in gone.iter() {
+                act.unsubscribe(id);
+            }
+
             if act.clients.len() > 0 {
                 act.hb = Instant::now();
             } else if Instant::now().duration_since(act.hb) > TIMEOUT {
@@ -90,6 +462,7 @@ impl Actor for Document {
 #[rtype(result = "()")]
 pub struct SubscribeMsg {
     pub id: String,
+    pub user_id: String,
     pub client: Addr<Client>
 }
 
@@ -101,7 +474,13 @@ impl Handler<SubscribeMsg> for Document {
         msg: SubscribeMsg,
         _ctx: &mut Context<Document>
     ) {
-        self.clients.insert(msg.id, msg.client);
+        let SubscribeMsg { id, user_id, client } = msg;
+        // Keep the operations after the current version until the client has
+        // caught up, even if it doesn't send a request.
+        let version = self.version();
+        self.client_versions.entry(id.clone()).or_insert(version);
+        self.users.insert(id.clone(), user_id);
+        self.clients.insert(id, client);
     }
 }
 
@@ -119,7 +498,7 @@ impl Handler<UnsubscribeMsg> for Document {
         msg: UnsubscribeMsg,
         _ctx: &mut Context<Document>
     ) {
-        self.clients.remove(&msg.id);
+        self.unsubscribe(&msg.id);
     }
 }
 
@@ -127,6 +506,7 @@ impl Handler<UnsubscribeMsg> for Document {
 #[rtype(result = "()")]
 pub struct GetDocMsg {
     pub client: Addr<Client>,
+    pub client_id: String,
     pub req_id: usize
 }
 
@@ -138,14 +518,137 @@ impl Handler<GetDocMsg> for Document {
         msg: GetDocMsg,
         _ctx: &mut Context<Document>
     ) {
-        let version = self.deleted_ops + self.operations.len();
-        let GetDocMsg { client, req_id } = msg;
+        let version = self.version();
+        let GetDocMsg { client, client_id, req_id } = msg;
+        let role = self.users
+            .get(&client_id)
+            .and_then(|user_id| self.acl.lock().unwrap().role(user_id));
+        let role = match role {
+            Some(role) => role,
+            None => {
+                client.do_send(forbidden(req_id, "view the doc"));
+                return;
+            }
+        };
+        self.client_versions.insert(client_id.clone(), version);
         let msg = RpcResponseMsg::Doc {
             id: req_id,
             version,
+            role,
             doc: self.doc.clone()
         };
         client.do_send(msg);
+        // Otherwise the diagnostics are pushed once the analysis is done.
+        if let Some(analysis) = self.current_analysis() {
+            let msg = RpcResponseMsg::Diagnostics {
+                version,
+                diagnostics: analysis.diagnostics.clone()
+            };
+            client.do_send(msg);
+        }
+        // The states of the other clients, so the new client doesn't have
+        // to wait for their next update.
+        self.awareness
+            .iter()
+            .filter(|(id, _)| *id != &client_id)
+            .filter_map(|(id, state)| {
+                let user_id = self.users.get(id)?;
+                Some(RpcResponseMsg::Awareness {
+                    client_id: id.clone(),
+                    user_id: user_id.clone(),
+                    state: Some(state.clone())
+                })
+            })
+            .for_each(|msg| client.do_send(msg));
+    }
+}
+
+#[derive(MessageTrait)]
+#[rtype(result = "()")]
+pub struct GetOperationsMsg {
+    pub client: Addr<Client>,
+    pub client_id: String,
+    pub req_id: usize,
+    pub since_version: usize
+}
+
+impl Handler<GetOperationsMsg> for Document {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: GetOperationsMsg,
+        _ctx: &mut Context<Document>
+    ) {
+        let GetOperationsMsg {
+            client,
+            client_id,
+            req_id,
+            since_version
+        } = msg;
+        if !self.allows(&client_id, Role::Viewer) {
+            client.do_send(forbidden(req_id, "view the doc"));
+            return;
+        }
+        let version = self.version();
+        let msg = if since_version < self.deleted_ops {
+            RpcResponseMsg::Error {
+                id: Some(req_id),
+                code: RpcErrorCode::SnapshotRequired,
+                msg: format!("Operations before version {} have been compacted, please refetch", self.deleted_ops)
+            }
+        } else if since_version > version {
+            RpcResponseMsg::Error {
+                id: Some(req_id),
+                code: RpcErrorCode::InvalidRequest,
+                msg: format!("Version {} is newer than the doc version {}", since_version, version)
+            }
+        } else {
+            let acked = self.client_versions.entry(client_id).or_insert(since_version);
+            *acked = since_version.max(*acked);
+            RpcResponseMsg::Operations {
+                id: req_id,
+                version,
+                operations: self.operations[since_version - self.deleted_ops..].to_vec(),
+                authors: self.authors[since_version - self.deleted_ops..].to_vec()
+            }
+        };
+        client.do_send(msg);
+    }
+}
+
+#[derive(MessageTrait)]
+#[rtype(result = "()")]
+pub struct GetSchemasMsg {
+    pub client: Addr<Client>,
+    pub client_id: String,
+    pub req_id: usize
+}
+
+impl Handler<GetSchemasMsg> for Document {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: GetSchemasMsg,
+        _ctx: &mut Context<Document>
+    ) {
+        let GetSchemasMsg { client, client_id, req_id } = msg;
+        if !self.allows(&client_id, Role::Viewer) {
+            client.do_send(forbidden(req_id, "view the doc"));
+            return;
+        }
+        match self.current_analysis() {
+            Some(analysis) => {
+                let msg = RpcResponseMsg::Schemas {
+                    id: Some(req_id),
+                    version: analysis.version,
+                    schemas: analysis.schemas.clone()
+                };
+                client.do_send(msg);
+            }
+            None => self.schema_requests.push((client, req_id))
+        }
     }
 }
 
@@ -153,6 +656,8 @@ impl Handler<GetDocMsg> for Document {
 #[rtype(result = "()")]
 pub struct UpdateDocMsg {
     pub client_id: String,
+    /// The id of the user who made the update.
+    pub author: String,
     pub req_id: usize,
     pub version: usize,
     pub operations: Vec<Operation>
@@ -164,10 +669,11 @@ impl Handler<UpdateDocMsg> for Document {
     fn handle(
         &mut self,
         msg: UpdateDocMsg,
-        _ctx: &mut Context<Document>
+        ctx: &mut Context<Document>
     ) {
         let UpdateDocMsg {
             client_id,
+            author,
             req_id,
             version,
             operations
@@ -176,14 +682,14 @@ impl Handler<UpdateDocMsg> for Document {
             if let Some(client) = self.clients.get(&client_id) {
                 let msg = RpcResponseMsg::Error {
                     id: Some(req_id),
-                    code: RpcErrorCode::InvalidRequest,
-                    msg: String::from("Unsyncable")
+                    code: RpcErrorCode::SnapshotRequired,
+                    msg: String::from("Unsyncable, please refetch")
                 };
                 client.do_send(msg);
             }
             return;
         }
-        if version > self.deleted_ops + self.operations.len() {
+        if version > self.version() {
             if let Some(client) = self.clients.get(&client_id) {
                 let msg = RpcResponseMsg::Error {
                     id: Some(req_id),
@@ -194,6 +700,15 @@ impl Handler<UpdateDocMsg> for Document {
             }
             return;
         }
+        if !self.allows(&client_id, Role::Editor) {
+            if let Some(client) = self.clients.get(&client_id) {
+                client.do_send(forbidden(req_id, "edit the doc"));
+            }
+            return;
+        }
+        // The client has seen every operation up to `version`.
+        let acked = self.client_versions.entry(client_id.clone()).or_insert(version);
+        *acked = version.max(*acked);
         if let Err(e) = validate_sequence(&operations) {
             if let Some(client) = self.clients.get(&client_id) {
                 let msg = RpcResponseMsg::Error {
@@ -206,7 +721,7 @@ impl Handler<UpdateDocMsg> for Document {
             return;
         }
         let preceding_ops = &self.operations[version-self.deleted_ops..];
-        let mut transformed_ops = transform_batch(operations, preceding_ops);
+        let transformed_ops = transform_batch(operations, preceding_ops);
         match self.doc.execute_operations(transformed_ops.clone()) {
             Ok(_undo_ops) => {
                 self.clients
@@ -222,11 +737,19 @@ impl Handler<UpdateDocMsg> for Document {
                             version: self.deleted_ops
                                 + self.operations.len()
                                 + transformed_ops.len(),
+                            author: author.clone(),
                             operations: transformed_ops.clone()
                         };
                         client.do_send(msg);
                     });
-                self.operations.append(&mut transformed_ops);
+                let version = self.version();
+                self.operations.extend(transformed_ops.iter().cloned());
+                self.authors.extend(transformed_ops.iter().map(|_| Some(author.clone())));
+                if transformed_ops.iter().any(changes_graph) {
+                    self.edited = true;
+                    self.request_analysis(ctx);
+                }
+                self.persist(version, author, transformed_ops);
             }
             Err(e) => {
                 if let Some(client) = self.clients.get(&client_id) {
@@ -248,7 +771,8 @@ pub struct QueryMsg {
     pub client: Addr<Client>,
     pub client_id: String,
     pub req_id: usize,
-    pub node_id: String
+    pub node_id: String,
+    pub view: View
 }
 
 impl Handler<QueryMsg> for Document {
@@ -259,14 +783,69 @@ impl Handler<QueryMsg> for Document {
         msg: QueryMsg,
         _ctx: &mut Context<Document>
     ) {
-        let QueryMsg { client, client_id, req_id, node_id } = msg;
+        let QueryMsg { client, client_id, req_id, node_id, view } = msg;
+        if !self.allows(&client_id, Role::Viewer) {
+            client.do_send(forbidden(req_id, "query the doc"));
+            return;
+        }
         match self.doc.extract_nodes(&node_id) {
             Ok(nodes) => {
                 let msg = JobMsg(Arc::new(Job {
-                    client,
+                    client: Some(client),
                     client_id,
+                    doc_path: self.path.clone(),
                     job_id: req_id,
                     job_kind: JobKind::Query {
+                        nodes,
+                        node_id,
+                        view
+                    },
+                }));
+                <Executor as SystemService>::from_registry()
+                    .do_send(msg);
+            }
+            Err(e) => {
+                let msg = RpcResponseMsg::Error {
+                    id: Some(req_id),
+                    code: RpcErrorCode::InvalidRequest,
+                    msg: e.to_string()
+                };
+                client.do_send(msg);
+            }
+        }
+    }
+}
+
+#[derive(MessageTrait)]
+#[rtype(result = "()")]
+pub struct MaterializeMsg {
+    pub client: Addr<Client>,
+    pub client_id: String,
+    pub req_id: usize,
+    pub node_id: String
+}
+
+impl Handler<MaterializeMsg> for Document {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: MaterializeMsg,
+        _ctx: &mut Context<Document>
+    ) {
+        let MaterializeMsg { client, client_id, req_id, node_id } = msg;
+        if !self.allows(&client_id, Role::Editor) {
+            client.do_send(forbidden(req_id, "write files"));
+            return;
+        }
+        match self.doc.extract_nodes(&node_id) {
+            Ok(nodes) => {
+                let msg = JobMsg(Arc::new(Job {
+                    client: Some(client),
+                    client_id,
+                    doc_path: self.path.clone(),
+                    job_id: req_id,
+                    job_kind: JobKind::Materialize {
                         nodes,
                         node_id
                     },
@@ -304,9 +883,14 @@ impl Handler<ReadFileMsg> for Document {
         _ctx: &mut Context<Document>
     ) {
         let ReadFileMsg { client, client_id, req_id, filename } = msg;
+        if !self.allows(&client_id, Role::Viewer) {
+            client.do_send(forbidden(req_id, "read files"));
+            return;
+        }
         let msg = JobMsg(Arc::new(Job {
-            client,
+            client: Some(client),
             client_id,
+            doc_path: self.path.clone(),
             job_id: req_id,
             job_kind: JobKind::ReadFile { filename }
         }));
@@ -315,83 +899,221 @@ impl Handler<ReadFileMsg> for Document {
     }
 }
 
-fn demo_doc() -> Doc {
-    let mut doc = Doc::new();
-    let ops = vec![
-        Operation::InsertNode {
-            node: Node::LoadCsv {
-                id: "demo_1".to_string(),
-                position: Position {
-                    x: 0.0,
-                    y: 0.0
-                },
-                filename: "Forbes 2000.csv".to_string(),
-                outputs: HashSet::new()
+#[derive(MessageTrait)]
+#[rtype(result = "()")]
+pub struct GetAclMsg {
+    pub client: Addr<Client>,
+    pub client_id: String,
+    pub req_id: usize
+}
+
+impl Handler<GetAclMsg> for Document {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: GetAclMsg,
+        _ctx: &mut Context<Document>
+    ) {
+        let GetAclMsg { client, client_id, req_id } = msg;
+        if !self.allows(&client_id, Role::Viewer) {
+            client.do_send(forbidden(req_id, "view the doc"));
+            return;
+        }
+        let msg = RpcResponseMsg::Acl {
+            id: Some(req_id),
+            roles: self.acl.lock().unwrap().roles.clone()
+        };
+        client.do_send(msg);
+    }
+}
+
+/// Share the doc with a user, change their role, or revoke their access with
+/// no role.  Only owners can do this.
+#[derive(MessageTrait)]
+#[rtype(result = "()")]
+pub struct SetRoleMsg {
+    pub client: Addr<Client>,
+    pub client_id: String,
+    pub req_id: usize,
+    pub user_id: String,
+    pub role: Option<Role>
+}
+
+impl Handler<SetRoleMsg> for Document {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: SetRoleMsg,
+        _ctx: &mut Context<Document>
+    ) {
+        let SetRoleMsg { client, client_id, req_id, user_id, role } = msg;
+        if !self.allows(&client_id, Role::Owner) {
+            client.do_send(forbidden(req_id, "share the doc"));
+            return;
+        }
+        let res = self.acl.lock().unwrap().set_role(user_id, role);
+        if let Err(e) = res {
+            let msg = RpcResponseMsg::Error {
+                id: Some(req_id),
+                code: RpcErrorCode::InvalidRequest,
+                msg: e.to_string()
+            };
+            client.do_send(msg);
+            return;
+        }
+        self.save_acl();
+
+        // Drop the clients of users who no longer have access.
+        let acl = self.acl.lock().unwrap().clone();
+        let revoked: Vec<String> = self.users
+            .iter()
+            .filter(|(_, user_id)| acl.role(user_id).is_none())
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in revoked.iter() {
+            self.drop_awareness(id);
+            self.users.remove(id);
+            self.client_versions.remove(id);
+            if let Some(client) = self.clients.remove(id) {
+                let msg = RpcResponseMsg::Error {
+                    id: None,
+                    code: RpcErrorCode::Forbidden,
+                    msg: String::from("Your access to the doc has been revoked")
+                };
+                client.do_send(msg);
             }
-        },
-        Operation::InsertIndex {
-            id: "demo_1".to_string(),
-            index: 0
-        },
-        Operation::InsertNode {
-            node: Node::Aggregate {
-                id: "demo_2".to_string(),
-                position: Position {
-                    x: 256.0 + 64.0,
-                    y: 0.0
-                },
-                input: None,
-                aggregates: vec![
-                    Aggregate {
-                        column: "Industry".to_string(),
-                        computation: AggregateComputation::Group,
-                        alias: "".to_string()
-                    },
-                    Aggregate {
-                        column: "Market Value (Billions)".to_string(),
-                        computation: AggregateComputation::Sum,
-                        alias: "".to_string()
+        }
+
+        self.clients
+            .iter()
+            .for_each(|(id, client)| {
+                let msg = RpcResponseMsg::Acl {
+                    id: if id == &client_id { Some(req_id) } else { None },
+                    roles: acl.roles.clone()
+                };
+                client.do_send(msg);
+            });
+    }
+}
+
+/// Awareness is fanned out to the other clients and never becomes part of the
+/// operation history.
+#[derive(MessageTrait)]
+#[rtype(result = "()")]
+pub struct SetAwarenessMsg {
+    pub client_id: String,
+    pub state: Awareness
+}
+
+impl Handler<SetAwarenessMsg> for Document {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: SetAwarenessMsg,
+        _ctx: &mut Context<Document>
+    ) {
+        let SetAwarenessMsg { client_id, mut state } = msg;
+        if !self.allows(&client_id, Role::Viewer) {
+            return;
+        }
+        state.bound(|id| self.doc.has_node(id));
+        self.awareness.insert(client_id.clone(), state.clone());
+        self.broadcast_awareness(&client_id, Some(state));
+    }
+}
+
+/// Source files in the project directory have been added, removed or
+/// rewritten.  The paths are canonical.
+#[derive(Clone, MessageTrait)]
+#[rtype(result = "()")]
+pub struct FilesChangedMsg {
+    pub paths: HashSet<PathBuf>
+}
+
+impl Handler<FilesChangedMsg> for Document {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: FilesChangedMsg,
+        ctx: &mut Context<Document>
+    ) {
+        self.changed_paths.extend(msg.paths);
+        self.request_analysis(ctx);
+    }
+}
+
+#[derive(MessageTrait)]
+#[rtype(result = "()")]
+pub struct ExportMsg {
+    pub client_id: String,
+    pub job_id: usize,
+    pub node_id: String,
+    pub format: ExportFormat,
+    pub sender: ExportSender
+}
+
+impl Handler<ExportMsg> for Document {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: ExportMsg,
+        _ctx: &mut Context<Document>
+    ) {
+        let ExportMsg { client_id, job_id, node_id, format, sender } = msg;
+        match self.doc.extract_nodes(&node_id) {
+            Ok(nodes) => {
+                let msg = JobMsg(Arc::new(Job {
+                    client: None,
+                    client_id,
+                    doc_path: self.path.clone(),
+                    job_id,
+                    job_kind: JobKind::Export {
+                        nodes,
+                        node_id,
+                        format,
+                        sender
                     }
-                ],
-                outputs: HashSet::new()
+                }));
+                <Executor as SystemService>::from_registry()
+                    .do_send(msg);
             }
-        },
-        Operation::InsertIndex {
-            id: "demo_2".to_string(),
-            index: 0
-        },
-        Operation::SetInput {
-            id: "demo_2".to_string(),
-            name: InputName::Primary,
-            input: Some("demo_1".to_string())
-        },
-        Operation::InsertNode {
-            node: Node::Sort {
-                id: "demo_3".to_string(),
-                position: Position {
-                    x: 2.0 * (256.0 + 64.0),
-                    y: 0.0
-                },
-                input: None,
-                sorters: vec![
-                    Sorter {
-                        column: "Market Value (Billions)".to_string(),
-                        direction: SortDirection::Desc
-                    }
-                ],
-                outputs: HashSet::new()
+            Err(e) => {
+                let _ = sender.try_send(Err(e));
             }
-        },
-        Operation::InsertIndex {
-            id: "demo_3".to_string(),
-            index: 0
-        },
-        Operation::SetInput {
-            id: "demo_3".to_string(),
-            name: InputName::Primary,
-            input: Some("demo_2".to_string())
-        }
-    ];
-    doc.execute_operations(ops).unwrap();
-    doc
+        }
+    }
+}
+
+fn analyze(doc: &Doc, context: &ExecutionContext, version: usize) -> Analysis {
+    let schemas = doc.schemas(context);
+    let diagnostics = doc.validate_schemas(&schemas);
+    let schemas = schemas
+        .into_iter()
+        .map(|(id, schema)| (id, NodeSchema::from(schema)))
+        .collect();
+    let hashes = doc.hashes(context);
+    Analysis { version, schemas, diagnostics, hashes }
+}
+
+/// Moving nodes around doesn't change any schema or result.
+fn changes_graph(operation: &Operation) -> bool {
+    !matches!(
+        operation,
+        Operation::SetPosition { id: _, position: _ }
+            | Operation::InsertIndex { id: _, index: _ }
+            | Operation::DeleteIndex { id: _, index: _ }
+    )
+}
+
+fn forbidden(req_id: usize, action: &str) -> RpcResponseMsg {
+    RpcResponseMsg::Error {
+        id: Some(req_id),
+        code: RpcErrorCode::Forbidden,
+        msg: format!("You aren't allowed to {}", action)
+    }
 }
@@ -7,42 +7,91 @@ use actix::Message as MessageTrait;
 use actix::Handler;
 use actix::Supervised;
 use actix::SystemService;
-use query::doc::Position;
+use actix_web::web::Bytes;
+use query::cache::ResultCache;
+use query::context::CancellationToken;
+use query::context::ExecutionContext;
+use query::doc::View;
 use query::doc::collect;
+use query::doc::collect_cached;
+use query::doc::collect_page;
+use query::doc::materialize;
 use query::doc::Node;
+use query::error::PoldaError;
+use query::export::ExportFormat;
+use query::export::write_frame;
 use std::collections::HashMap;
-use std::collections::HashSet;
 use std::collections::VecDeque;
+use std::io;
+use std::io::Write;
+use std::mem;
 use std::sync::Arc;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
 use tokio::sync::mpsc;
-use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task;
 
 use crate::Client;
 use crate::client::RpcErrorCode;
 use crate::client::RpcResponseMsg;
+use crate::sources::source_node;
 
 const ROW_LIMIT: usize = 100;
+/// Max rows of a query result page.
+const MAX_PAGE_SIZE: usize = 10_000;
+/// Upper bounds of the workers a single client or document can occupy.  Both
+/// are also capped at half of the workers.
+const MAX_JOBS_PER_CLIENT: usize = 2;
+const MAX_JOBS_PER_DOC: usize = 4;
+/// Size of the chunks an export is sent in.
+const EXPORT_CHUNK_SIZE: usize = 64 * 1024;
+/// An export gives up its worker if the download doesn't take a chunk
+/// within this time.
+const EXPORT_SEND_TIMEOUT: Duration = Duration::from_secs(30);
+const EXPORT_SEND_RETRY: Duration = Duration::from_millis(10);
+
+/// Receives the written file of an export job in chunks.  An empty chunk
+/// marks the end of the file.
+pub type ExportSender = mpsc::Sender<Result<Bytes, PoldaError>>;
 
 #[derive(Debug)]
 pub enum JobKind {
     Query {
+        nodes: HashMap<String, Node>,
+        node_id: String,
+        view: View
+    },
+    Materialize {
         nodes: HashMap<String, Node>,
         node_id: String
     },
     ReadFile {
         filename: String
+    },
+    /// Write the whole result of a node.  The file is sent through `sender`
+    /// instead of to a client.
+    Export {
+        nodes: HashMap<String, Node>,
+        node_id: String,
+        format: ExportFormat,
+        sender: ExportSender
     }
 }
 
 pub struct Job {
-    pub client: Addr<Client>,
+    /// `None` for jobs that don't belong to a websocket client.
+    pub client: Option<Addr<Client>>,
     pub client_id: String,
+    /// Path of the document the job belongs to.
+    pub doc_path: String,
     pub job_id: usize,
     pub job_kind: JobKind
 }
 
-/// The currently running job is kept in the queue until the next job is
-/// retrieved.  This way there's no job queued in the mpsc channel.
+/// Jobs waiting for a worker in the order they were submitted.
 #[derive(Default)]
 struct Queue {
     jobs: HashMap<(String, usize), Arc<Job>>,
@@ -50,25 +99,15 @@ struct Queue {
 }
 
 impl Queue {
-    fn len(&self) -> usize {
-        self.index.len()
-    }
-
-    fn next(&mut self) -> Option<Arc<Job>> {
-        // Remove the finished job.
-        if let Some(id) = self.index.pop_front() {
-            if let Some(job) = self.jobs.remove(&id) {
-                return Some(job);
-            }
-        }
-        while let Some(id) = self.index.front() {
-            if let Some(job) = self.jobs.get(id) {
-                return Some(job.clone());
-            } else {
-                self.index.pop_front();
-            }
-        }
-        None
+    /// Take the oldest job that satisfies `is_allowed`.
+    fn next<F: Fn(&Job) -> bool>(&mut self, is_allowed: F) -> Option<Arc<Job>> {
+        // Drop the ids of removed jobs.
+        self.index.retain(|id| self.jobs.contains_key(id));
+        let position = self.index
+            .iter()
+            .position(|id| is_allowed(self.jobs.get(id).unwrap()))?;
+        let id = self.index.remove(position).unwrap();
+        self.jobs.remove(&id)
     }
 
     fn push(&mut self, job: Arc<Job>) {
@@ -81,29 +120,115 @@ impl Queue {
     }
 }
 
+struct RunningJob {
+    job: Arc<Job>,
+    token: CancellationToken
+}
 
-#[derive(Default)]
+/// Runs jobs on a pool of `workers` blocking threads.  A client or a
+/// document can't occupy more than its share of the workers, so a slow job
+/// doesn't hold up everybody else.
 pub struct Executor {
-    jobs: Queue,
-    sender: Option<Sender<Arc<Job>>>
+    queue: Queue,
+    running: HashMap<(String, usize), RunningJob>,
+    workers: usize,
+    context: ExecutionContext,
+    cache: Arc<Mutex<ResultCache>>
 }
 
-impl Actor for Executor {
-    type Context = Context<Executor>;
+impl Executor {
+    pub fn new(context: ExecutionContext, cache: ResultCache, workers: usize) -> Executor {
+        Executor {
+            queue: Queue::default(),
+            running: HashMap::new(),
+            workers: workers.max(1),
+            context,
+            cache: Arc::new(Mutex::new(cache))
+        }
+    }
+
+    /// Mark the next queued job that may run as running.
+    fn start_next(&mut self) -> Option<(Arc<Job>, CancellationToken)> {
+        if self.running.len() >= self.workers {
+            return None;
+        }
+        let max_per_client = (self.workers / 2).clamp(1, MAX_JOBS_PER_CLIENT);
+        let max_per_doc = (self.workers / 2).clamp(1, MAX_JOBS_PER_DOC);
+        let running = &self.running;
+        let job = self.queue.next(|job| {
+            let client_jobs = running
+                .values()
+                .filter(|r| r.job.client_id == job.client_id)
+                .count();
+            let doc_jobs = running
+                .values()
+                .filter(|r| r.job.doc_path == job.doc_path)
+                .count();
+            client_jobs < max_per_client && doc_jobs < max_per_doc
+        })?;
+
+        let token = CancellationToken::default();
+        let running_job = RunningJob {
+            job: job.clone(),
+            token: token.clone()
+        };
+        self.running.insert((job.client_id.clone(), job.job_id), running_job);
+        Some((job, token))
+    }
 
-    fn started(&mut self, ctx: &mut Self::Context) {
-        let (sender, mut receiver) = mpsc::channel(100);
-        self.sender = Some(sender);
-        let executor = ctx.address();
-        ctx.spawn(wrap_future(async move {
-            while let Some(job) = receiver.recv().await {
-                handle_job(job.as_ref());
-                executor.do_send(NextJobMsg);
+    /// Cancel a queued or running job.  Returns `false` if there's no such
+    /// job or it has already been canceled.
+    fn cancel(&mut self, client_id: String, job_id: usize) -> bool {
+        if self.queue.remove(client_id.clone(), job_id).is_some() {
+            return true;
+        }
+        match self.running.get(&(client_id, job_id)) {
+            // Polars can't interrupt a collect, so the job keeps its worker
+            // until its thread returns.  The client doesn't have to wait for
+            // that, the worker drops the result.
+            Some(running_job) if !running_job.token.is_canceled() => {
+                running_job.token.cancel();
+                true
             }
-        }));
+            _ => false
+        }
+    }
+
+    /// Start queued jobs while there are idle workers.
+    fn schedule(&mut self, ctx: &mut Context<Executor>) {
+        while let Some((job, token)) = self.start_next() {
+            let executor = ctx.address();
+            let context = self.context.with_token(token);
+            let cache = self.cache.clone();
+            ctx.spawn(wrap_future(async move {
+                let client_id = job.client_id.clone();
+                let job_id = job.job_id;
+                let res = task::spawn_blocking(move || {
+                    handle_job(job.as_ref(), &context, &cache);
+                }).await;
+                if let Err(e) = res {
+                    log::error!("job {} of client {} failed: {}", job_id, client_id, e);
+                }
+                executor.do_send(JobDoneMsg { client_id, job_id });
+            }));
+        }
+    }
+}
+
+impl Default for Executor {
+    /// One worker per CPU.
+    fn default() -> Executor {
+        let workers = thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1);
+        Executor::new(ExecutionContext::default(), ResultCache::default(), workers)
     }
 }
 
+impl Actor for Executor {
+    type Context = Context<Executor>;
+}
+
 #[derive(MessageTrait)]
 #[rtype(result = "()")]
 pub struct JobMsg(pub Arc<Job>);
@@ -116,23 +241,16 @@ impl Handler<JobMsg> for Executor {
         msg: JobMsg,
         ctx: &mut Context<Executor>
     ) {
-        if self.jobs.len() == 0 {
-            if let Some(sender) = &self.sender {
-                let sender = sender.clone();
-                ctx.spawn(wrap_future(async move {
-                    sender.send(msg.0).await.ok();
-                }));
-            }
-        } else {
-            self.jobs.push(msg.0);
-        }
+        self.queue.push(msg.0);
+        self.schedule(ctx);
     }
 }
 
 #[derive(MessageTrait)]
 #[rtype(result = "()")]
 pub struct CancelJobMsg {
-    pub client: Addr<Client>,
+    /// `None` for jobs that don't belong to a websocket client.
+    pub client: Option<Addr<Client>>,
     pub client_id: String,
     pub job_id: usize
 }
@@ -150,54 +268,84 @@ impl Handler<CancelJobMsg> for Executor {
             client_id,
             job_id
         } = msg;
-        if let Some(_) = self.jobs.remove(client_id, job_id) {
-            let msg = RpcResponseMsg::JobCanceled { id: msg.job_id };
-            client.do_send(msg);
+        if self.cancel(client_id, job_id) {
+            if let Some(client) = client {
+                let msg = RpcResponseMsg::JobCanceled { id: job_id };
+                client.do_send(msg);
+            }
         }
     }
 }
 
+/// Drop cached results, e.g. of nodes that have been edited.
 #[derive(MessageTrait)]
 #[rtype(result = "()")]
-pub struct NextJobMsg;
+pub struct InvalidateCacheMsg {
+    pub keys: Vec<u64>
+}
 
-impl Handler<NextJobMsg> for Executor {
+impl Handler<InvalidateCacheMsg> for Executor {
     type Result = ();
 
     fn handle(
         &mut self,
-        _msg: NextJobMsg,
-        ctx: &mut Context<Executor>
+        msg: InvalidateCacheMsg,
+        _ctx: &mut Context<Executor>
     ) {
-        if let Some(job) = self.jobs.next() {
-            if let Some(sender) = &self.sender {
-                let sender = sender.clone();
-                ctx.spawn(wrap_future(async move {
-                    sender.send(job).await.ok();
-                }));
-            }
+        let mut cache = self.cache.lock().unwrap();
+        for key in msg.keys.into_iter() {
+            cache.remove(key);
         }
     }
 }
 
+#[derive(MessageTrait)]
+#[rtype(result = "()")]
+struct JobDoneMsg {
+    client_id: String,
+    job_id: usize
+}
+
+impl Handler<JobDoneMsg> for Executor {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: JobDoneMsg,
+        ctx: &mut Context<Executor>
+    ) {
+        let JobDoneMsg { client_id, job_id } = msg;
+        self.running.remove(&(client_id, job_id));
+        self.schedule(ctx);
+    }
+}
+
 impl Supervised for Executor {}
 impl SystemService for Executor {}
 
-fn handle_job(job: &Job) {
+fn handle_job(job: &Job, context: &ExecutionContext, cache: &Mutex<ResultCache>) {
     let Job {
         client,
         client_id: _,
+        doc_path: _,
         job_id,
         job_kind
     } = job;
     let msg = match job_kind {
-        JobKind::Query { nodes, node_id } => {
-            let res = collect(&nodes, &node_id, Some(ROW_LIMIT));
+        JobKind::Query { nodes, node_id, view } => {
+            let view = View {
+                offset: view.offset,
+                limit: Some(view.limit.unwrap_or(ROW_LIMIT).min(MAX_PAGE_SIZE)),
+                sorters: view.sorters.clone()
+            };
+            let res = collect_page(&nodes, &node_id, &view, context, cache);
             match res {
-                Ok(df) => {
+                Ok(page) => {
                     RpcResponseMsg::QueryResult {
                         id: job_id.clone(),
-                        data: df
+                        data: page.data,
+                        offset: page.offset,
+                        total_rows: page.total_rows
                     }
                 }
                 Err(e) => {
@@ -211,20 +359,49 @@ fn handle_job(job: &Job) {
             }
         }
 
+        JobKind::Materialize { nodes, node_id } => {
+            match materialize(nodes, node_id, context, cache) {
+                Ok(rows) => {
+                    RpcResponseMsg::Materialized {
+                        id: *job_id,
+                        rows
+                    }
+                }
+                Err(e) => {
+                    RpcResponseMsg::Error {
+                        id: Some(*job_id),
+                        code: RpcErrorCode::InternalError,
+                        msg: e.to_string()
+                    }
+                }
+            }
+        }
+
+        JobKind::Export { nodes, node_id, format, sender } => {
+            let mut writer = ChunkWriter {
+                sender: sender.clone(),
+                buf: vec![]
+            };
+            let res = collect(nodes, node_id, None, context)
+                .and_then(|mut df| write_frame(&mut writer, &mut df, *format))
+                .and_then(|_| Ok(writer.flush()?))
+                .and_then(|_| Ok(writer.send(Bytes::new())?));
+            if let Err(e) = res {
+                // The receiver is gone if the download was aborted, and may
+                // be full if it's stalled.  Without the end marker the
+                // download fails either way.
+                let _ = sender.try_send(Err(e));
+            }
+            return;
+        }
+
         JobKind::ReadFile { filename } => {
             let node_id = String::from("a");
-            let node = Node::LoadCsv {
-                id: node_id.clone(),
-                position: Position {
-                    x: 0.0,
-                    y: 0.0
-                },
-                filename: filename.clone(),
-                outputs: HashSet::new()
-            };
-            let mut nodes = HashMap::new();
-            nodes.insert(node_id.clone(), node);
-            let res = collect(&nodes, &node_id, Some(ROW_LIMIT));
+            let res = source_node(&node_id, filename).and_then(|node| {
+                let mut nodes = HashMap::new();
+                nodes.insert(node_id.clone(), node);
+                collect_cached(&nodes, &node_id, Some(ROW_LIMIT), context, cache)
+            });
 
             match res {
                 Ok(df) => {
@@ -244,6 +421,156 @@ fn handle_job(job: &Job) {
             }
         }
     };
-    client.do_send(msg);
+    let client = match client {
+        Some(client) => client,
+        None => return
+    };
+    // The executor has already told the client that the job was canceled.
+    if !context.token().is_canceled() {
+        client.do_send(msg);
+    }
+}
+
+/// Sends the written file of an export in chunks.  Writing fails once the
+/// download is gone or stalls, which stops the export.
+struct ChunkWriter {
+    sender: ExportSender,
+    buf: Vec<u8>
+}
+
+impl ChunkWriter {
+    /// Wait for room in the channel without holding the worker forever.
+    fn send(&self, chunk: Bytes) -> io::Result<()> {
+        let deadline = Instant::now() + EXPORT_SEND_TIMEOUT;
+        let mut item = Ok(chunk);
+        loop {
+            match self.sender.try_send(item) {
+                Ok(()) => return Ok(()),
+                Err(TrySendError::Closed(_)) => {
+                    return Err(io::Error::new(io::ErrorKind::BrokenPipe, "The export was aborted"));
+                }
+                Err(TrySendError::Full(_)) if Instant::now() >= deadline => {
+                    return Err(io::Error::new(io::ErrorKind::TimedOut, "The download stalled"));
+                }
+                Err(TrySendError::Full(back)) => {
+                    item = back;
+                    thread::sleep(EXPORT_SEND_RETRY);
+                }
+            }
+        }
+    }
+}
+
+impl Write for ChunkWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= EXPORT_CHUNK_SIZE {
+            self.flush()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(mem::take(&mut self.buf));
+        self.send(chunk)
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn job(client_id: &str, doc_path: &str, job_id: usize) -> Arc<Job> {
+        Arc::new(Job {
+            client: None,
+            client_id: client_id.to_string(),
+            doc_path: doc_path.to_string(),
+            job_id,
+            job_kind: JobKind::ReadFile {
+                filename: String::from("data.csv")
+            }
+        })
+    }
+
+    fn with_jobs(workers: usize, jobs: Vec<Arc<Job>>) -> Executor {
+        let mut executor = Executor::new(ExecutionContext::default(), ResultCache::default(), workers);
+        for job in jobs.into_iter() {
+            executor.queue.push(job);
+        }
+        executor
+    }
+
+    fn start_next(executor: &mut Executor) -> Option<(String, usize)> {
+        executor.start_next().map(|(job, _)| (job.client_id.clone(), job.job_id))
+    }
+
+    fn id(client_id: &str, job_id: usize) -> Option<(String, usize)> {
+        Some((client_id.to_string(), job_id))
+    }
+
+    #[test]
+    fn job_limits() {
+        // At most 2 jobs per client and 4 per doc.
+        let mut executor = with_jobs(8, vec![
+            job("a", "x", 0),
+            job("a", "x", 1),
+            job("a", "x", 2),
+            job("b", "x", 0),
+            job("c", "x", 0),
+            job("c", "x", 1),
+            job("d", "y", 0)
+        ]);
+        assert_eq!(start_next(&mut executor), id("a", 0));
+        assert_eq!(start_next(&mut executor), id("a", 1));
+        assert_eq!(start_next(&mut executor), id("b", 0));
+        assert_eq!(start_next(&mut executor), id("c", 0));
+        assert_eq!(start_next(&mut executor), id("d", 0));
+        assert_eq!(start_next(&mut executor), None);
+
+        // The oldest job that fits goes first.
+        executor.running.remove(&("a".to_string(), 0));
+        assert_eq!(start_next(&mut executor), id("a", 2));
+        assert_eq!(start_next(&mut executor), None);
+        executor.running.remove(&("b".to_string(), 0));
+        assert_eq!(start_next(&mut executor), id("c", 1));
+
+        // Every worker is busy.
+        let mut executor = with_jobs(2, vec![
+            job("a", "x", 0),
+            job("b", "y", 0),
+            job("c", "z", 0)
+        ]);
+        assert_eq!(start_next(&mut executor), id("a", 0));
+        assert_eq!(start_next(&mut executor), id("b", 0));
+        assert_eq!(start_next(&mut executor), None);
+    }
+
+    #[test]
+    fn cancel_jobs() {
+        let mut executor = with_jobs(2, vec![
+            job("a", "x", 0),
+            job("a", "x", 1),
+            job("b", "y", 0)
+        ]);
+        assert_eq!(start_next(&mut executor), id("a", 0));
+
+        // A queued job is removed.
+        assert!(executor.cancel("a".to_string(), 1));
+        assert!(!executor.cancel("a".to_string(), 1));
+
+        // A running job is canceled once and keeps its worker.
+        assert!(executor.cancel("a".to_string(), 0));
+        assert!(!executor.cancel("a".to_string(), 0));
+        assert!(executor.running[&("a".to_string(), 0)].token.is_canceled());
+        assert_eq!(start_next(&mut executor), id("b", 0));
+        assert_eq!(start_next(&mut executor), None);
+
+        assert!(!executor.cancel("c".to_string(), 0));
+        executor.running.remove(&("a".to_string(), 0));
+        assert_eq!(start_next(&mut executor), None);
+        assert!(executor.queue.jobs.is_empty());
+    }
+}
@@ -0,0 +1,179 @@
+use actix::SystemService;
+use actix_web::Error;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::error::ErrorBadRequest;
+use actix_web::error::ErrorForbidden;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::error::ErrorUnauthorized;
+use actix_web::http::header::ContentDisposition;
+use actix_web::http::header::DispositionParam;
+use actix_web::http::header::DispositionType;
+use actix_web::web;
+use actix_web::web::Bytes;
+use futures_util::Stream;
+use futures_util::StreamExt;
+use query::error::PoldaError;
+use query::export::ExportFormat;
+use serde::Deserialize;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::task::Context;
+use std::task::Poll;
+use tokio::sync::mpsc;
+
+use crate::auth::Auth;
+use crate::broker::Broker;
+use crate::broker::OpenDocumentMsg;
+use crate::document::ExportMsg;
+use crate::executor::CancelJobMsg;
+use crate::executor::Executor;
+
+static EXPORT_IDS: AtomicUsize = AtomicUsize::new(0);
+
+/// Chunks of an export that can be waiting for a slow download.
+const EXPORT_BUFFER: usize = 8;
+
+#[derive(Debug, Deserialize)]
+pub struct ExportParams {
+    path: String,
+    node_id: String,
+    format: ExportFormat
+}
+
+/// `GET /export?path=<doc>&node_id=<node>&format=<csv|ipc|ndjson|parquet>`
+/// downloads the whole result of a node.
+pub async fn export(
+    req: HttpRequest,
+    params: web::Query<ExportParams>,
+    auth: web::Data<Auth>
+) -> Result<HttpResponse, Error> {
+    let user = match auth.authenticate(&req) {
+        Some(user) => user,
+        None => return Err(ErrorUnauthorized("Invalid or missing token"))
+    };
+    let ExportParams { path, node_id, format } = params.into_inner();
+    // Exports are queued as jobs of a pseudo client per user, so a user's
+    // exports share the per-client limit of the executor.  Websocket client
+    // ids are alphanumeric.
+    let client_id = format!("http:{}", user.id);
+
+    // Every role can export, but exporting never creates a doc.
+    let msg = OpenDocumentMsg {
+        path: path.clone(),
+        user_id: user.id,
+        create: false
+    };
+    let doc = <Broker as SystemService>::from_registry()
+        .send(msg)
+        .await
+        .map_err(ErrorInternalServerError)?
+        .map_err(|e| match e {
+            PoldaError::Forbidden(_) => ErrorForbidden(e),
+            _ => ErrorBadRequest(e)
+        })?;
+
+    let (sender, receiver) = mpsc::channel(EXPORT_BUFFER);
+    let job_id = EXPORT_IDS.fetch_add(1, Ordering::Relaxed);
+    let msg = ExportMsg {
+        client_id: client_id.clone(),
+        job_id,
+        node_id: node_id.clone(),
+        format,
+        sender
+    };
+    doc.send(msg).await.map_err(ErrorInternalServerError)?;
+    let mut stream = ExportStream {
+        receiver,
+        client_id,
+        job_id,
+        done: false
+    };
+    // Errors before the first chunk, e.g. of the query, are still a proper
+    // response.
+    let first = match stream.receiver.recv().await {
+        Some(Ok(chunk)) => {
+            // An empty chunk is the end of an empty file.
+            stream.done = chunk.is_empty();
+            chunk
+        }
+        Some(Err(e)) => {
+            stream.done = true;
+            return Err(ErrorBadRequest(e));
+        }
+        None => {
+            stream.done = true;
+            return Err(ErrorInternalServerError("The export was aborted"));
+        }
+    };
+
+    let stem = Path::new(&path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let filename = format!("{}_{}.{}", stem, node_id, format.extension());
+    let disposition = ContentDisposition {
+        disposition: DispositionType::Attachment,
+        parameters: vec![DispositionParam::Filename(filename)]
+    };
+
+    Ok(
+        HttpResponse::Ok()
+            .content_type(format.content_type())
+            .insert_header(disposition)
+            .streaming(futures_util::stream::once(async { Ok(first) }).chain(stream))
+    )
+}
+
+/// The chunks of an export job.  The job is canceled if the download is
+/// dropped before the last chunk.
+struct ExportStream {
+    receiver: mpsc::Receiver<Result<Bytes, PoldaError>>,
+    client_id: String,
+    job_id: usize,
+    done: bool
+}
+
+impl Stream for ExportStream {
+    type Item = Result<Bytes, PoldaError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        match self.receiver.poll_recv(cx) {
+            Poll::Ready(Some(Ok(chunk))) if chunk.is_empty() => {
+                self.done = true;
+                Poll::Ready(None)
+            }
+            Poll::Ready(Some(Err(e))) => {
+                self.done = true;
+                Poll::Ready(Some(Err(e)))
+            }
+            Poll::Ready(None) => {
+                // The job ended without the end marker, so the file is
+                // incomplete.
+                self.done = true;
+                let msg = String::from("The export was aborted");
+                Poll::Ready(Some(Err(PoldaError::InternalError(msg))))
+            }
+            item => item
+        }
+    }
+}
+
+impl Drop for ExportStream {
+    fn drop(&mut self) {
+        if !self.done {
+            let msg = CancelJobMsg {
+                client: None,
+                client_id: self.client_id.clone(),
+                job_id: self.job_id
+            };
+            <Executor as SystemService>::from_registry()
+                .do_send(msg);
+        }
+    }
+}
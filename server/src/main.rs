@@ -1,5 +1,6 @@
 use actix_files::NamedFile;
 use actix::Actor;
+use actix::SystemRegistry;
 use actix_cors::Cors;
 use actix_web::App;
 use actix_web::Error;
@@ -11,14 +12,34 @@ use actix_web::Responder;
 use actix_web::web;
 use actix_web_actors::ws;
 use std::env;
+use std::path::PathBuf;
+use std::thread;
 
+mod acl;
+mod auth;
 mod broker;
 mod client;
 mod document;
 mod executor;
+mod export;
+mod sources;
+mod storage;
+mod upload;
+mod watcher;
 
+use auth::Auth;
+use auth::Jwt;
+use auth::StaticTokens;
+use auth::User;
 use client::Client;
 use broker::Broker;
+use executor::Executor;
+use watcher::Watcher;
+use query::cache::DEFAULT_CACHE_BUDGET;
+use query::cache::ResultCache;
+use query::context::ExecutionContext;
+use upload::DEFAULT_MAX_UPLOAD_SIZE;
+use upload::UploadConfig;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -31,10 +52,58 @@ async fn main() -> std::io::Result<()> {
         .unwrap_or(8080);
     let origin = env::var("ORIGIN")
         .unwrap_or(String::from("http://localhost:3000"));
+    let project_dir = env::var("PROJECT_DIR")
+        .map(PathBuf::from)
+        .unwrap_or(PathBuf::from("."));
+    let workers = env::var("WORKERS")
+        .map(|w| w.parse::<usize>().expect("Invalid WORKERS environment variable"))
+        .unwrap_or_else(|_| thread::available_parallelism().map(|n| n.get()).unwrap_or(4));
+    // Memory budget of the query result cache in MiB.
+    let cache_budget = env::var("CACHE_SIZE")
+        .map(|s| s.parse::<usize>().expect("Invalid CACHE_SIZE environment variable") * 1024 * 1024)
+        .unwrap_or(DEFAULT_CACHE_BUDGET);
+    // Max size of an upload in MiB.
+    let max_upload_size = env::var("MAX_UPLOAD_SIZE")
+        .map(|s| s.parse::<usize>().expect("Invalid MAX_UPLOAD_SIZE environment variable") * 1024 * 1024)
+        .unwrap_or(DEFAULT_MAX_UPLOAD_SIZE);
+    let upload_config = UploadConfig { max_size: max_upload_size };
+    // A JSON file with static API tokens, and the secret of HMAC signed JWTs.
+    let mut auth = Auth::new();
+    if let Ok(file) = env::var("AUTH_TOKENS") {
+        let tokens = StaticTokens::load(&PathBuf::from(&file))
+            .unwrap_or_else(|e| panic!("Invalid AUTH_TOKENS file {}: {}", file, e));
+        auth = auth.with(tokens);
+    }
+    if let Ok(secret) = env::var("JWT_SECRET") {
+        auth = auth.with(Jwt::new(secret.as_bytes()));
+    }
+    // The owner of documents saved before they had an ACL.  Without
+    // authentication everyone is the anonymous user.
+    let admin = env::var("ADMIN_USER")
+        .ok()
+        .or_else(|| (!auth.is_enabled()).then(|| User::anonymous().id));
+    let auth = web::Data::new(auth);
 
     log::info!("starting HTTP server at http://{}:{}", hostname, port);
 
-    let broker = Broker::new().start();
+    log::info!("using project directory {}", project_dir.display());
+
+    log::info!("running queries on {} workers", workers);
+
+    if !auth.is_enabled() {
+        log::warn!("authentication is disabled, set AUTH_TOKENS or JWT_SECRET to enable it");
+    }
+
+    if admin.is_none() {
+        log::warn!("documents without an owner can't be opened, set ADMIN_USER to migrate them");
+    }
+
+    let context = ExecutionContext::new(project_dir);
+    let executor = Executor::new(context.clone(), ResultCache::new(cache_budget), workers).start();
+    SystemRegistry::set(executor);
+    let broker = Broker::new(context.clone(), admin).start();
+    SystemRegistry::set(broker.clone());
+    let _watcher = Watcher::new(context.clone()).start();
 
     HttpServer::new(move || {
         let cors = Cors::default()
@@ -49,11 +118,21 @@ async fn main() -> std::io::Result<()> {
 
         App::new()
             .app_data(web::Data::new(broker.clone()))
-            .wrap(Logger::default())
+            .app_data(web::Data::new(context.clone()))
+            .app_data(web::Data::new(upload_config.clone()))
+            .app_data(auth.clone())
+            // The default format without the query string, which may hold
+            // an auth token.
+            .wrap(
+                Logger::new("%a \"%{method}xi %U\" %s %b \"%{Referer}i\" \"%{User-Agent}i\" %T")
+                    .custom_request_replace("method", |req| req.method().to_string())
+            )
             .wrap(cors)
             .service(web::resource("/").to(index))
             .route("/client", web::get().to(client))
             .route("/ws", web::get().to(ws))
+            .route("/export", web::get().to(export::export))
+            .route("/upload", web::post().to(upload::upload))
     })
     .bind((hostname, port))?
     .shutdown_timeout(60)
@@ -75,9 +154,15 @@ async fn client() -> impl Responder {
 async fn ws(
     req: HttpRequest,
     stream: web::Payload,
+    context: web::Data<ExecutionContext>,
+    auth: web::Data<Auth>
 ) -> Result<HttpResponse, Error> {
+    let user = match auth.authenticate(&req) {
+        Some(user) => user,
+        None => return Ok(HttpResponse::Unauthorized().body("Invalid or missing token"))
+    };
     ws::start(
-        Client::new(),
+        Client::new(context.get_ref().clone(), user),
         &req,
         stream,
     )
@@ -0,0 +1,265 @@
+use once_cell::sync::Lazy;
+use query::column::Column;
+use query::context::ExecutionContext;
+use query::doc::CsvOptions;
+use query::doc::Node;
+use query::doc::Position;
+use query::error::PoldaError;
+use query::query::Schema;
+use query::source::SourceFormat;
+use serde::Deserialize;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+use tokio::task;
+
+/// Subdirectories deeper than this aren't listed.
+const MAX_DEPTH: usize = 8;
+
+/// Peeked columns by path.  A peek is reused while the size and modification
+/// time of the file stay the same.
+static PEEKS: Lazy<Mutex<HashMap<PathBuf, Peek>>> = Lazy::new(|| {
+    Mutex::new(HashMap::new())
+});
+
+struct Peek {
+    size: u64,
+    modified: Option<u64>,
+    columns: Option<Vec<Column>>
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Source {
+    /// Path relative to the project directory.
+    pub filename: String,
+    pub format: SourceFormat,
+    pub size: u64,
+    /// Milliseconds since the unix epoch.
+    pub modified: Option<u64>,
+    /// `None` if the schema can't be inferred, e.g. of DuckDB files.
+    pub columns: Option<Vec<Column>>
+}
+
+/// List the source files in `dir` (relative to the project directory) and its
+/// subdirectories.  Hidden files and directories are skipped.  With `refresh`
+/// the columns of every file are peeked again.  This reads the file system,
+/// so don't call it on an actor's thread.
+pub fn list_sources(context: &ExecutionContext, dir: &str, refresh: bool) -> Result<Vec<Source>, PoldaError> {
+    let root = context.project_dir().canonicalize()?;
+    let dir = if dir.is_empty() {
+        root.clone()
+    } else {
+        context.resolve(dir)?
+    };
+    let is_root = dir == root;
+
+    let mut sources = vec![];
+    let mut seen = HashSet::new();
+    let mut dirs = vec![(dir, 0)];
+
+    while let Some((dir, depth)) = dirs.pop() {
+        let entries = match dir.read_dir() {
+            Ok(entries) => entries,
+            Err(_) => continue
+        };
+        for entry in entries.flatten() {
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let path = entry.path();
+            match entry.file_type() {
+                Ok(ftype) if ftype.is_dir() => {
+                    if depth < MAX_DEPTH {
+                        dirs.push((path, depth + 1));
+                    }
+                    continue;
+                }
+                Ok(_) => (),
+                Err(_) => continue
+            }
+
+            let format = match SourceFormat::from_path(&path) {
+                Some(format) => format,
+                None => continue
+            };
+            let filename = match path.strip_prefix(&root) {
+                Ok(filename) => filename.to_string_lossy().replace('\\', "/"),
+                Err(_) => continue
+            };
+            // Symlinks must not lead out of the project directory.
+            if context.resolve(&filename).is_err() {
+                continue;
+            }
+            let metadata = match fs::metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(_) => continue
+            };
+            let size = metadata.len();
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+                .map(|modified| modified.as_millis() as u64);
+            let columns = peek_columns(context, &path, &filename, size, modified, refresh);
+
+            seen.insert(path);
+            sources.push(Source { filename, format, size, modified, columns });
+        }
+    }
+
+    // Forget files that are gone.
+    if is_root {
+        PEEKS.lock().unwrap().retain(|path, _| seen.contains(path));
+    }
+
+    sources.sort_by(|a, b| a.filename.cmp(&b.filename));
+    Ok(sources)
+}
+
+/// `list_sources` on a blocking thread.
+pub async fn list_sources_blocking(
+    context: ExecutionContext,
+    dir: String,
+    refresh: bool
+) -> Result<Vec<Source>, PoldaError> {
+    task::spawn_blocking(move || list_sources(&context, &dir, refresh))
+        .await
+        .map_err(|e| PoldaError::InternalError(e.to_string()))?
+}
+
+fn peek_columns(
+    context: &ExecutionContext,
+    path: &Path,
+    filename: &str,
+    size: u64,
+    modified: Option<u64>,
+    refresh: bool
+) -> Option<Vec<Column>> {
+    if !refresh {
+        if let Some(peek) = PEEKS.lock().unwrap().get(path) {
+            if peek.size == size && peek.modified == modified {
+                return peek.columns.clone();
+            }
+        }
+    }
+
+    let columns = source_node("a", filename)
+        .and_then(|node| Schema::try_from_node(&node, vec![], context))
+        .map(|schema| schema.columns().to_vec())
+        .ok();
+    let peek = Peek {
+        size,
+        modified,
+        columns: columns.clone()
+    };
+    PEEKS.lock().unwrap().insert(path.to_path_buf(), peek);
+    columns
+}
+
+/// Build a source node that reads `filename` based on its extension.  Files
+/// with an unknown extension are read as CSV.
+pub fn source_node(id: &str, filename: &str) -> Result<Node, PoldaError> {
+    let id = id.to_string();
+    let position = Position {
+        x: 0.0,
+        y: 0.0
+    };
+    let filename = filename.to_string();
+    let outputs = HashSet::new();
+    let node = match SourceFormat::from_path(&filename) {
+        Some(SourceFormat::DuckDb) => {
+            return Err(PoldaError::QueryError(format!("Can't read \"{}\" without a table name", filename)));
+        }
+        Some(SourceFormat::Ipc) => Node::LoadIpc { id, position, filename, outputs },
+        Some(SourceFormat::Json) => Node::LoadJson { id, position, filename, outputs },
+        Some(SourceFormat::Parquet) => Node::LoadParquet { id, position, filename, outputs },
+        Some(SourceFormat::Csv) | None => {
+            Node::LoadCsv {
+                id,
+                position,
+                filename,
+                options: CsvOptions::default(),
+                outputs
+            }
+        }
+    };
+    Ok(node)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::fs::FileTimes;
+    use super::*;
+
+    fn filenames(sources: &[Source]) -> Vec<&str> {
+        sources.iter().map(|source| source.filename.as_str()).collect()
+    }
+
+    fn column_names(source: &Source) -> Vec<&str> {
+        source.columns
+            .as_ref()
+            .unwrap()
+            .iter()
+            .map(|column| column.name.as_str())
+            .collect()
+    }
+
+    // The peek cache is shared, so every case runs in one test.
+    #[test]
+    fn list_project_sources() {
+        let base = std::env::temp_dir().join(format!("polda-sources-{}", std::process::id()));
+        let root = base.join("project");
+        let outside = base.join("outside");
+        fs::create_dir_all(root.join(".hidden")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(root.join("a.csv"), "x,y\n1,a\n").unwrap();
+        fs::write(root.join(".b.csv"), "x\n1\n").unwrap();
+        fs::write(root.join(".hidden/c.csv"), "x\n1\n").unwrap();
+        fs::write(root.join("notes.txt"), "x\n1\n").unwrap();
+        fs::write(outside.join("d.csv"), "x\n1\n").unwrap();
+        let deepest = (1..=MAX_DEPTH).fold(root.clone(), |dir, i| dir.join(format!("l{}", i)));
+        fs::create_dir_all(deepest.join("too_deep")).unwrap();
+        fs::write(deepest.join("e.csv"), "x\n1\n").unwrap();
+        fs::write(deepest.join("too_deep/f.csv"), "x\n1\n").unwrap();
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(outside.join("d.csv"), root.join("link.csv")).unwrap();
+            std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+        }
+        let context = ExecutionContext::new(root.clone());
+
+        // Hidden entries, other files, symlinks out of the root and
+        // directories beyond `MAX_DEPTH` are skipped.
+        let sources = list_sources(&context, "", false).unwrap();
+        assert_eq!(filenames(&sources), vec!["a.csv", "l1/l2/l3/l4/l5/l6/l7/l8/e.csv"]);
+        assert_eq!(column_names(&sources[0]), vec!["x", "y"]);
+        let sources = list_sources(&context, "l1/l2/l3/l4/l5/l6/l7/l8", false).unwrap();
+        assert_eq!(filenames(&sources), vec!["l1/l2/l3/l4/l5/l6/l7/l8/e.csv", "l1/l2/l3/l4/l5/l6/l7/l8/too_deep/f.csv"]);
+
+        // The listed directory must be in the project directory.
+        assert!(list_sources(&context, "..", false).is_err());
+        assert!(list_sources(&context, "../outside", false).is_err());
+        #[cfg(unix)]
+        assert!(list_sources(&context, "link", false).is_err());
+
+        // A file with the same size and modification time isn't peeked again.
+        let file = root.join("a.csv");
+        let modified = fs::metadata(&file).unwrap().modified().unwrap();
+        fs::write(&file, "z,w\n1,a\n").unwrap();
+        File::options().write(true).open(&file).unwrap()
+            .set_times(FileTimes::new().set_modified(modified))
+            .unwrap();
+        let sources = list_sources(&context, "", false).unwrap();
+        assert_eq!(column_names(&sources[0]), vec!["x", "y"]);
+        let sources = list_sources(&context, "", true).unwrap();
+        assert_eq!(column_names(&sources[0]), vec!["z", "w"]);
+
+        fs::remove_dir_all(&base).unwrap();
+    }
+}
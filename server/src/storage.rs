@@ -0,0 +1,324 @@
+use query::doc::Doc;
+use query::doc::Operation;
+use query::error::PoldaError;
+use serde::Deserialize;
+use serde::Serialize;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::io::Write;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::acl::Acl;
+
+/// Bump this when the layout of `DocFile` changes.  Older files must still be
+/// readable by `load_doc`.
+const FORMAT_VERSION: u32 = 2;
+const DOC_EXTENSION: &str = "polda";
+const LOG_EXTENSION: &str = "log";
+
+/// The on-disk representation of a document.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DocFile {
+    pub format_version: u32,
+    pub version: usize,
+    pub doc: Doc,
+    /// Added in format version 2.
+    #[serde(default)]
+    pub acl: Acl
+}
+
+/// Resolve a document path sent by a client into a file under the project
+/// directory.  The path must be relative and must not contain `..`.
+pub fn doc_file_path(project_dir: &Path, path: &str) -> Result<PathBuf, PoldaError> {
+    if path.is_empty() {
+        return Err(PoldaError::DocError(String::from("Document path can't be empty")));
+    }
+
+    // Rebuilt from the components, so e.g. `a//b` and `a/b/` are `a/b`.
+    let mut file = project_dir.to_path_buf();
+    for component in Path::new(path).components() {
+        if let Component::Normal(name) = component {
+            file.push(name);
+            continue;
+        }
+        return Err(PoldaError::DocError(format!("Invalid document path \"{}\"", path)));
+    }
+
+    if file.extension().and_then(|ext| ext.to_str()) != Some(DOC_EXTENSION) {
+        let mut filename = file.as_os_str().to_os_string();
+        filename.push(".");
+        filename.push(DOC_EXTENSION);
+        file = PathBuf::from(filename);
+    }
+    Ok(file)
+}
+
+/// The file of a document relative to the project directory.  Every path
+/// that resolves to the same file, e.g. `a` and `a.polda`, has the same key.
+pub fn doc_key(project_dir: &Path, path: &str) -> Result<String, PoldaError> {
+    let file = doc_file_path(project_dir, path)?;
+    let relative = file
+        .strip_prefix(project_dir)
+        .map_err(|_| PoldaError::DocError(format!("Invalid document path \"{}\"", path)))?;
+    Ok(relative.to_string_lossy().to_string())
+}
+
+/// The operation log lives next to the document snapshot.
+pub fn log_file_path(doc_file: &Path) -> PathBuf {
+    let mut filename = doc_file.as_os_str().to_os_string();
+    filename.push(".");
+    filename.push(LOG_EXTENSION);
+    PathBuf::from(filename)
+}
+
+/// Load a document, its version and its ACL.  Return `None` if the file
+/// doesn't exist.
+pub fn load_doc(file: &Path) -> Result<Option<(Doc, usize, Acl)>, PoldaError> {
+    let content = match fs::read(file) {
+        Ok(content) => content,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into())
+    };
+    let doc_file: DocFile = serde_json::from_slice(&content)
+        .map_err(|e| PoldaError::ParseError(format!("Failed to parse \"{}\": {}", file.display(), e)))?;
+    if doc_file.format_version > FORMAT_VERSION {
+        return Err(PoldaError::DocError(format!(
+            "Document \"{}\" has an unsupported format version {}",
+            file.display(),
+            doc_file.format_version
+        )));
+    }
+    Ok(Some((doc_file.doc, doc_file.version, doc_file.acl)))
+}
+
+/// Save a document atomically: write into a temporary file next to the
+/// target, flush it to disk, and rename it over the target.
+pub fn save_doc(file: &Path, doc: &Doc, version: usize, acl: &Acl) -> Result<(), PoldaError> {
+    let doc_file = DocFile {
+        format_version: FORMAT_VERSION,
+        version,
+        doc: doc.clone(),
+        acl: acl.clone()
+    };
+    let content = serde_json::to_vec(&doc_file)
+        .map_err(|e| PoldaError::InternalError(format!("Failed to serialize document: {}", e)))?;
+    write_atomic(file, &content)
+}
+
+pub fn write_atomic(file: &Path, content: &[u8]) -> Result<(), PoldaError> {
+    if let Some(dir) = file.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut tmp_name = file.as_os_str().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_file = PathBuf::from(tmp_name);
+
+    let mut f = File::create(&tmp_file)?;
+    f.write_all(content)?;
+    f.sync_all()?;
+    drop(f);
+    fs::rename(&tmp_file, file)?;
+
+    // Persist the rename itself.
+    if let Some(dir) = file.parent() {
+        if let Ok(dir) = File::open(dir) {
+            dir.sync_all().ok();
+        }
+    }
+    Ok(())
+}
+
+/// A batch of operations that were applied on top of `version`.  Entries
+/// written before authentication existed have no author.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    pub version: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    pub operations: Vec<Operation>
+}
+
+/// Append-only log of the operations applied to a document.  Every entry is
+/// stored as a little-endian `u32` length followed by the JSON encoded entry.
+pub struct OperationLog {
+    file: PathBuf
+}
+
+impl OperationLog {
+    pub fn new(file: PathBuf) -> OperationLog {
+        OperationLog { file }
+    }
+
+    pub fn append(&self, entry: &LogEntry) -> Result<(), PoldaError> {
+        let record = encode_entry(entry)?;
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut f = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.file)?;
+        f.write_all(&record)?;
+        f.sync_data()?;
+        Ok(())
+    }
+
+    /// Read all the entries.  A partially written entry at the end of the
+    /// log (e.g. after a crash) is dropped and the log is rewritten without
+    /// it.  A complete entry that can't be decoded is an error, since
+    /// dropping it would lose operations.
+    pub fn read(&self) -> Result<Vec<LogEntry>, PoldaError> {
+        let content = match fs::read(&self.file) {
+            Ok(content) => content,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into())
+        };
+
+        let mut entries = vec![];
+        let mut offset = 0;
+        let mut truncated = false;
+
+        while offset < content.len() {
+            let start = offset + 4;
+            if start > content.len() {
+                truncated = true;
+                break;
+            }
+            let mut len = [0_u8; 4];
+            len.copy_from_slice(&content[offset..start]);
+            let end = start + u32::from_le_bytes(len) as usize;
+            if end > content.len() {
+                truncated = true;
+                break;
+            }
+            let entry = serde_json::from_slice::<LogEntry>(&content[start..end])
+                .map_err(|e| PoldaError::DocError(format!("Operation log {} is corrupt at offset {}: {}", self.file.display(), offset, e)))?;
+            entries.push(entry);
+            offset = end;
+        }
+
+        if truncated {
+            log::warn!("dropping a partially written entry from {}", self.file.display());
+            self.rewrite(&entries)?;
+        }
+
+        Ok(entries)
+    }
+
+    /// Atomically replace the whole log.
+    pub fn rewrite(&self, entries: &[LogEntry]) -> Result<(), PoldaError> {
+        let mut content = vec![];
+        for entry in entries.iter() {
+            content.append(&mut encode_entry(entry)?);
+        }
+        write_atomic(&self.file, &content)
+    }
+}
+
+fn encode_entry(entry: &LogEntry) -> Result<Vec<u8>, PoldaError> {
+    let json = serde_json::to_vec(entry)
+        .map_err(|e| PoldaError::InternalError(format!("Failed to serialize operations: {}", e)))?;
+    let len = u32::try_from(json.len())
+        .map_err(|_| PoldaError::InternalError(String::from("Operation log entry is too large")))?;
+    let mut record = Vec::with_capacity(json.len() + 4);
+    record.extend_from_slice(&len.to_le_bytes());
+    record.extend_from_slice(&json);
+    Ok(record)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn doc_keys() {
+        let project_dir = Path::new("/project");
+        let key = |path| doc_key(project_dir, path).unwrap();
+        assert_eq!(key("a"), "a.polda");
+        assert_eq!(key("a.polda"), "a.polda");
+        assert_eq!(key("a//b"), key("a/b"));
+        assert_eq!(key("a/b/"), key("a/b"));
+        assert!(doc_key(project_dir, "../a").is_err());
+        assert!(doc_key(project_dir, "/a").is_err());
+        assert!(doc_key(project_dir, "").is_err());
+    }
+
+    fn entry(version: usize) -> LogEntry {
+        LogEntry {
+            version,
+            author: Some(format!("user{}", version)),
+            operations: vec![]
+        }
+    }
+
+    fn versions(entries: &[LogEntry]) -> Vec<usize> {
+        entries.iter().map(|entry| entry.version).collect()
+    }
+
+    fn temp_log(name: &str) -> OperationLog {
+        let file = std::env::temp_dir().join(format!("polda-{}-{}.log", name, std::process::id()));
+        let _ = fs::remove_file(&file);
+        OperationLog::new(file)
+    }
+
+    #[test]
+    fn append_and_read() {
+        let log = temp_log("log-round-trip");
+        assert!(log.read().unwrap().is_empty());
+        log.append(&entry(0)).unwrap();
+        log.append(&entry(3)).unwrap();
+        let entries = log.read().unwrap();
+        assert_eq!(versions(&entries), vec![0, 3]);
+        assert_eq!(entries[1].author.as_deref(), Some("user3"));
+        fs::remove_file(&log.file).unwrap();
+    }
+
+    #[test]
+    fn drop_torn_tail() {
+        let log = temp_log("log-torn-tail");
+        log.append(&entry(0)).unwrap();
+        log.append(&entry(1)).unwrap();
+        let intact = fs::read(&log.file).unwrap();
+
+        // A record cut off in its body.
+        let mut record = encode_entry(&entry(2)).unwrap();
+        record.truncate(record.len() - 1);
+        fs::write(&log.file, [intact.clone(), record].concat()).unwrap();
+        assert_eq!(versions(&log.read().unwrap()), vec![0, 1]);
+        assert_eq!(fs::read(&log.file).unwrap(), intact);
+
+        // A record cut off in its length.
+        fs::write(&log.file, [intact.clone(), vec![1, 0]].concat()).unwrap();
+        assert_eq!(versions(&log.read().unwrap()), vec![0, 1]);
+        assert_eq!(fs::read(&log.file).unwrap(), intact);
+        fs::remove_file(&log.file).unwrap();
+    }
+
+    #[test]
+    fn reject_corrupt_entry() {
+        let log = temp_log("log-corrupt");
+        let garbage = b"{\"version\":";
+        let mut corrupt = (garbage.len() as u32).to_le_bytes().to_vec();
+        corrupt.extend_from_slice(garbage);
+        let first = encode_entry(&entry(0)).unwrap();
+        let last = encode_entry(&entry(1)).unwrap();
+
+        // In the middle.
+        let content = [first.clone(), corrupt.clone(), last].concat();
+        fs::write(&log.file, &content).unwrap();
+        assert!(matches!(log.read(), Err(PoldaError::DocError(_))));
+        assert_eq!(fs::read(&log.file).unwrap(), content);
+
+        // At the end, with an intact length.
+        let content = [first, corrupt].concat();
+        fs::write(&log.file, &content).unwrap();
+        assert!(matches!(log.read(), Err(PoldaError::DocError(_))));
+        assert_eq!(fs::read(&log.file).unwrap(), content);
+        fs::remove_file(&log.file).unwrap();
+    }
+}
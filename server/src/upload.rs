@@ -0,0 +1,253 @@
+use actix::SystemService;
+use actix_multipart::Multipart;
+use actix_web::Error;
+use actix_web::HttpRequest;
+use actix_web::HttpResponse;
+use actix_web::error::ErrorBadRequest;
+use actix_web::error::ErrorConflict;
+use actix_web::error::ErrorInternalServerError;
+use actix_web::error::ErrorPayloadTooLarge;
+use actix_web::error::ErrorUnauthorized;
+use actix_web::error::ErrorUnsupportedMediaType;
+use actix_web::web;
+use actix_web::web::Bytes;
+use futures_util::Stream;
+use futures_util::StreamExt;
+use query::context::ExecutionContext;
+use query::source::SourceFormat;
+use serde::Deserialize;
+use serde::Serialize;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use crate::auth::Auth;
+use crate::broker::Broker;
+use crate::broker::SourcesChangedMsg;
+
+/// 1 GiB.
+pub const DEFAULT_MAX_UPLOAD_SIZE: usize = 1024 * 1024 * 1024;
+
+static UPLOAD_IDS: AtomicUsize = AtomicUsize::new(0);
+
+#[derive(Debug, Clone)]
+pub struct UploadConfig {
+    /// Max total size of the files of a request in bytes.
+    pub max_size: usize
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UploadParams {
+    /// Directory relative to the project directory.  Defaults to the project
+    /// directory itself.
+    #[serde(default)]
+    dir: Option<String>,
+    #[serde(default)]
+    overwrite: bool
+}
+
+#[derive(Debug, Serialize)]
+struct UploadResponse {
+    filenames: Vec<String>
+}
+
+/// `POST /upload?dir=<dir>&overwrite=<bool>` stores the files of a multipart
+/// form in the project directory.  A file is written to a temporary file
+/// first and renamed when it's complete, so readers never see a partial file.
+/// Files stored before a failing file are kept.
+pub async fn upload(
+    req: HttpRequest,
+    mut payload: Multipart,
+    params: web::Query<UploadParams>,
+    context: web::Data<ExecutionContext>,
+    config: web::Data<UploadConfig>,
+    auth: web::Data<Auth>
+) -> Result<HttpResponse, Error> {
+    if auth.authenticate(&req).is_none() {
+        return Err(ErrorUnauthorized("Invalid or missing token"));
+    }
+    let UploadParams { dir, overwrite } = params.into_inner();
+    let mut filenames = vec![];
+    let res = store_files(&mut payload, &dir, overwrite, &context, config.max_size, &mut filenames).await;
+
+    if !filenames.is_empty() {
+        <Broker as SystemService>::from_registry()
+            .do_send(SourcesChangedMsg);
+    }
+    res?;
+    if filenames.is_empty() {
+        return Err(ErrorBadRequest("The request doesn't contain a file"));
+    }
+
+    Ok(HttpResponse::Ok().json(UploadResponse { filenames }))
+}
+
+/// Store the files of the form and add their names to `filenames`.
+async fn store_files(
+    payload: &mut Multipart,
+    dir: &Option<String>,
+    overwrite: bool,
+    context: &ExecutionContext,
+    max_size: usize,
+    filenames: &mut Vec<String>
+) -> Result<(), Error> {
+    let mut size = 0;
+
+    while let Some(field) = payload.next().await {
+        let mut field = field?;
+        let name = match field.content_disposition().and_then(|cd| cd.get_filename()) {
+            Some(name) => check_file_name(name)?,
+            // Not a file.
+            None => continue
+        };
+
+        let filename = match dir {
+            Some(dir) if !dir.is_empty() => format!("{}/{}", dir.trim_end_matches('/'), name),
+            _ => name.clone()
+        };
+        let path = context.resolve_target(&filename).map_err(ErrorBadRequest)?;
+
+        let tmp_path = path.with_file_name(format!(
+            ".{}.{}.upload",
+            name,
+            UPLOAD_IDS.fetch_add(1, Ordering::Relaxed)
+        ));
+        let res = write_field(&mut field, &tmp_path, &mut size, max_size).await;
+        let res = match res {
+            Ok(()) => store_file(&tmp_path, &path, &filename, overwrite).await,
+            Err(e) => Err(e)
+        };
+        let _ = fs::remove_file(&tmp_path).await;
+        res?;
+        filenames.push(filename);
+    }
+    Ok(())
+}
+
+/// The name to store an uploaded file as.  Any directory the client sent
+/// along with the file name is dropped.
+fn check_file_name(name: &str) -> Result<String, Error> {
+    let name = match Path::new(name).file_name().and_then(|name| name.to_str()) {
+        Some(name) if !name.starts_with('.') => name.to_string(),
+        _ => return Err(ErrorBadRequest(format!("Invalid file name \"{}\"", name)))
+    };
+    if SourceFormat::from_path(&name).is_none() {
+        return Err(ErrorUnsupportedMediaType(format!("\"{}\" isn't a supported source file", name)));
+    }
+    Ok(name)
+}
+
+/// Move a complete upload into place.  Without `overwrite` it's linked
+/// instead, which fails if the file exists, even if it was created while
+/// the upload was written.  The caller removes the temporary file.
+async fn store_file(tmp_path: &Path, path: &Path, filename: &str, overwrite: bool) -> Result<(), Error> {
+    let res = if overwrite {
+        fs::rename(tmp_path, path).await
+    } else {
+        fs::hard_link(tmp_path, path).await
+    };
+    match res {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+            Err(ErrorConflict(format!("File \"{}\" already exists", filename)))
+        }
+        Err(e) => Err(ErrorInternalServerError(e))
+    }
+}
+
+/// Write a file field.  `size` is the size of the request so far.
+async fn write_field<S, E>(
+    field: &mut S,
+    path: &Path,
+    size: &mut usize,
+    max_size: usize
+) -> Result<(), Error>
+where
+    S: Stream<Item = Result<Bytes, E>> + Unpin,
+    E: Into<Error>
+{
+    let mut file = fs::File::create(path)
+        .await
+        .map_err(ErrorInternalServerError)?;
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk.map_err(Into::into)?;
+        *size += chunk.len();
+        if *size > max_size {
+            return Err(ErrorPayloadTooLarge(format!("Uploads are limited to {} bytes", max_size)));
+        }
+        file.write_all(&chunk)
+            .await
+            .map_err(ErrorInternalServerError)?;
+    }
+    file.sync_all()
+        .await
+        .map_err(ErrorInternalServerError)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::error::PayloadError;
+    use actix_web::http::StatusCode;
+
+    fn status<T: std::fmt::Debug>(res: Result<T, Error>) -> StatusCode {
+        res.unwrap_err().as_response_error().status_code()
+    }
+
+    #[test]
+    fn file_names() {
+        assert_eq!(check_file_name("data.csv").unwrap(), "data.csv");
+        assert_eq!(check_file_name("../dir/data.parquet").unwrap(), "data.parquet");
+        assert_eq!(status(check_file_name(".hidden.csv")), StatusCode::BAD_REQUEST);
+        assert_eq!(status(check_file_name("..")), StatusCode::BAD_REQUEST);
+        assert_eq!(status(check_file_name("notes.txt")), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+        assert_eq!(status(check_file_name("data")), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[tokio::test]
+    async fn size_limit() {
+        let dir = std::env::temp_dir().join(format!("polda-upload-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.csv");
+        let chunks = || {
+            futures_util::stream::iter(vec![
+                Ok::<_, PayloadError>(Bytes::from_static(b"a,b\n")),
+                Ok(Bytes::from_static(b"1,2\n"))
+            ])
+        };
+
+        let mut size = 0;
+        write_field(&mut chunks(), &path, &mut size, 8).await.unwrap();
+        assert_eq!(size, 8);
+        assert_eq!(std::fs::read(&path).unwrap(), b"a,b\n1,2\n");
+
+        // The limit is on the whole request.
+        let res = write_field(&mut chunks(), &path, &mut size, 12).await;
+        assert_eq!(status(res), StatusCode::PAYLOAD_TOO_LARGE);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn no_overwrite() {
+        let dir = std::env::temp_dir().join(format!("polda-upload-store-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("data.csv");
+        let tmp_path = dir.join(".data.csv.0.upload");
+        std::fs::write(&path, "old").unwrap();
+        std::fs::write(&tmp_path, "new").unwrap();
+
+        let res = store_file(&tmp_path, &path, "data.csv", false).await;
+        assert_eq!(status(res), StatusCode::CONFLICT);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "old");
+
+        store_file(&tmp_path, &path, "data.csv", true).await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,122 @@
+use actix::Actor;
+use actix::AsyncContext;
+use actix::Context;
+use actix::Handler;
+use actix::Message as MessageTrait;
+use actix::SpawnHandle;
+use actix::SystemService;
+use notify::Event;
+use notify::EventKind;
+use notify::RecommendedWatcher;
+use notify::RecursiveMode;
+use notify::Watcher as _;
+use query::context::ExecutionContext;
+use query::source::SourceFormat;
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::broker::Broker;
+use crate::document::FilesChangedMsg;
+
+/// Changes are collected for this long before they are reported, so a file
+/// that is being written is reported once.
+const DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Watches the project directory for source files that are added, removed or
+/// rewritten.
+pub struct Watcher {
+    context: ExecutionContext,
+    watcher: Option<RecommendedWatcher>,
+    changed: HashSet<PathBuf>,
+    flush: Option<SpawnHandle>
+}
+
+impl Watcher {
+    pub fn new(context: ExecutionContext) -> Watcher {
+        Watcher {
+            context,
+            watcher: None,
+            changed: HashSet::new(),
+            flush: None
+        }
+    }
+
+    fn flush(&mut self) {
+        self.flush = None;
+        let paths: HashSet<PathBuf> = self.changed.drain().collect();
+        if !paths.is_empty() {
+            <Broker as SystemService>::from_registry()
+                .do_send(FilesChangedMsg { paths });
+        }
+    }
+}
+
+impl Actor for Watcher {
+    type Context = Context<Watcher>;
+
+    fn started(&mut self, ctx: &mut Context<Watcher>) {
+        let root = match self.context.project_dir().canonicalize() {
+            Ok(root) => root,
+            Err(e) => {
+                log::error!("failed to watch the project directory: {}", e);
+                return;
+            }
+        };
+
+        let addr = ctx.address();
+        let watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            match res {
+                Ok(event) if !matches!(event.kind, EventKind::Access(_)) => {
+                    addr.do_send(FsEventMsg { paths: event.paths });
+                }
+                Ok(_) => (),
+                Err(e) => log::error!("file watcher error: {}", e)
+            }
+        });
+        let res = watcher.and_then(|mut watcher| {
+            watcher.watch(&root, RecursiveMode::Recursive)?;
+            Ok(watcher)
+        });
+
+        match res {
+            Ok(watcher) => {
+                log::info!("watching {} for source changes", root.display());
+                self.watcher = Some(watcher);
+            }
+            Err(e) => log::error!("failed to watch {}: {}", root.display(), e)
+        }
+    }
+}
+
+#[derive(MessageTrait)]
+#[rtype(result = "()")]
+struct FsEventMsg {
+    paths: Vec<PathBuf>
+}
+
+impl Handler<FsEventMsg> for Watcher {
+    type Result = ();
+
+    fn handle(
+        &mut self,
+        msg: FsEventMsg,
+        ctx: &mut Context<Watcher>
+    ) {
+        // Only source files matter, not docs, logs or temporary files.
+        let paths = msg.paths
+            .into_iter()
+            .filter(|path| {
+                let is_hidden = path
+                    .file_name()
+                    .is_none_or(|name| name.to_string_lossy().starts_with('.'));
+                !is_hidden && SourceFormat::from_path(path).is_some()
+            });
+        self.changed.extend(paths);
+
+        if !self.changed.is_empty() && self.flush.is_none() {
+            let handle = ctx.run_later(DEBOUNCE, |act, _ctx| act.flush());
+            self.flush = Some(handle);
+        }
+    }
+}